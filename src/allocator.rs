@@ -0,0 +1,55 @@
+// Global allocator for `alloc` (Box, Vec, String): a bump allocator over a fixed 64 KiB
+// static. Freeing only counts allocations down; the whole heap comes back at once when
+// the last one is freed.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::interrupts::without_interrupts;
+
+pub const HEAP_SIZE: usize = 64 * 1024;
+
+static mut HEAP: [u8; HEAP_SIZE] = [0; HEAP_SIZE];
+
+pub struct BumpAllocator {
+    next: AtomicUsize, // offset of the first free byte in HEAP
+    allocations: AtomicUsize, // live allocations
+}
+
+impl BumpAllocator {
+    const fn new() -> Self {
+        Self { next: AtomicUsize::new(0), allocations: AtomicUsize::new(0) }
+    }
+}
+
+// Interrupts are held off so a handler that allocates can't hand out the same bytes twice
+unsafe impl GlobalAlloc for BumpAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        without_interrupts(|| {
+            let base = core::ptr::addr_of_mut!(HEAP) as usize;
+            let start = (base + self.next.load(Ordering::Relaxed)).next_multiple_of(layout.align());
+            let Some(end) = start.checked_add(layout.size()).filter(|&end| end <= base + HEAP_SIZE) else {
+                return core::ptr::null_mut(); // ends up in `alloc_error`
+            };
+            self.next.store(end - base, Ordering::Relaxed);
+            self.allocations.fetch_add(1, Ordering::Relaxed);
+            start as *mut u8
+        })
+    }
+
+    unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {
+        without_interrupts(|| {
+            if self.allocations.fetch_sub(1, Ordering::Relaxed) == 1 {
+                self.next.store(0, Ordering::Relaxed);
+            }
+        })
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: BumpAllocator = BumpAllocator::new();
+
+#[alloc_error_handler]
+fn alloc_error(layout: Layout) -> ! {
+    panic!("OUT OF SWAG MEMORY - no room for {} more bytes in the {} KiB heap", layout.size(), HEAP_SIZE / 1024);
+}
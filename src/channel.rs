@@ -0,0 +1,125 @@
+// Bounded queue for passing values from one task to another. `send` waits while it's
+// full and `recv` waits while it's empty, each parked on a waker the other side wakes.
+
+use core::future::poll_fn;
+use core::task::{Poll, Waker};
+
+use crate::ring_buffer::RingBuffer;
+use crate::spinlock::SpinLock;
+
+// One sending task and one receiving task, same as the `RingBuffer` underneath. Values
+// still queued when the channel is dropped are dropped with it.
+pub struct Channel<T, const N: usize> {
+    queue: RingBuffer<T, N>,
+    sender: SpinLock<Option<Waker>>,   // waiting for room
+    receiver: SpinLock<Option<Waker>>, // waiting for a value
+}
+
+impl<T, const N: usize> Channel<T, N> {
+    pub const fn new() -> Self {
+        Self { queue: RingBuffer::new(), sender: SpinLock::new(None), receiver: SpinLock::new(None) }
+    }
+
+    pub async fn send(&self, value: T) {
+        let mut value = Some(value);
+        poll_fn(|cx| {
+            // Parked first, so a `recv` making room right after a failed push still wakes us
+            *self.sender.lock() = Some(cx.waker().clone());
+            match self.queue.push(value.take().expect("polled after sending")) {
+                Ok(()) => {
+                    wake(&self.receiver);
+                    Poll::Ready(())
+                }
+                Err(back) => {
+                    value = Some(back);
+                    Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+
+    pub async fn recv(&self) -> T {
+        poll_fn(|cx| {
+            *self.receiver.lock() = Some(cx.waker().clone());
+            match self.queue.pop() {
+                Some(value) => {
+                    wake(&self.sender);
+                    Poll::Ready(value)
+                }
+                None => Poll::Pending,
+            }
+        })
+        .await
+    }
+}
+
+// The waker is taken out before waking, so the lock isn't held while it runs
+fn wake(parked: &SpinLock<Option<Waker>>) {
+    let waker = parked.lock().take();
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DefaultExecutor, yield_now};
+    use core::future::Future;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    // Two slots for six values, so the sender has to wait on the receiver a few times
+    #[test_case]
+    fn values_arrive_in_order() {
+        static CHANNEL: Channel<u32, 2> = Channel::new();
+        static RECEIVED: AtomicUsize = AtomicUsize::new(0);
+
+        let mut executor = DefaultExecutor::new();
+        let sender = executor
+            .spawn(async {
+                for i in 0..6 {
+                    CHANNEL.send(i * 10).await;
+                }
+            })
+            .unwrap();
+        let receiver = executor
+            .spawn(async {
+                for i in 0..6 {
+                    assert_eq!(CHANNEL.recv().await, i * 10);
+                    RECEIVED.fetch_add(1, Ordering::Relaxed);
+                    yield_now().await; // lets the sender fill up in between
+                }
+            })
+            .unwrap();
+        while !(executor.is_finished(sender) && executor.is_finished(receiver)) {
+            executor.run_pass();
+        }
+        assert_eq!(RECEIVED.load(Ordering::Relaxed), 6);
+        assert!(CHANNEL.queue.pop().is_none());
+    }
+
+    #[test_case]
+    fn unreceived_values_are_dropped() {
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+        struct Counted;
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                DROPS.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let channel: Channel<Counted, 4> = Channel::new();
+        {
+            let send = core::pin::pin!(async {
+                channel.send(Counted).await;
+                channel.send(Counted).await;
+            });
+            let mut context = core::task::Context::from_waker(Waker::noop());
+            assert!(send.poll(&mut context).is_ready()); // room for both, so no waiting
+        }
+        assert_eq!(DROPS.load(Ordering::Relaxed), 0);
+        drop(channel);
+        assert_eq!(DROPS.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,99 @@
+// CMOS (the RTC chip) and its battery-backed NVRAM
+
+use crate::interrupts::without_interrupts;
+use crate::port::{inb, outb};
+
+const CMOS_ADDRESS: u16 = 0x70;
+const CMOS_DATA: u16 = 0x71;
+
+const NMI_DISABLE: u8 = 0x80; // high bit of the address port masks NMIs
+
+// RTC registers
+const RTC_SECONDS: u8 = 0x00;
+const RTC_MINUTES: u8 = 0x02;
+const RTC_HOURS: u8 = 0x04;
+const RTC_STATUS_A: u8 = 0x0a;
+const RTC_STATUS_B: u8 = 0x0b;
+
+const UPDATE_IN_PROGRESS: u8 = 0x80; // in status A
+const STATUS_B_24_HOUR: u8 = 0x02;
+const STATUS_B_BINARY: u8 = 0x04; // otherwise the time registers are BCD
+const HOUR_PM: u8 = 0x80; // in the hours register, 12-hour mode only
+
+// General-purpose NVRAM bytes; below this are the RTC registers
+pub const NVRAM_START: u8 = 0x0e;
+pub const NVRAM_END: u8 = 0x3f;
+
+// Selecting a register and then reading it are two port accesses, so nothing else may
+// touch the CMOS in between
+fn read_register(register: u8) -> u8 {
+    without_interrupts(|| unsafe {
+        outb(CMOS_ADDRESS, NMI_DISABLE | register);
+        inb(CMOS_DATA)
+    })
+}
+
+fn write_register(register: u8, value: u8) {
+    without_interrupts(|| unsafe {
+        outb(CMOS_ADDRESS, NMI_DISABLE | register);
+        outb(CMOS_DATA, value);
+    })
+}
+
+// Offsets outside NVRAM_START..=NVRAM_END would hit the clock registers; reads give 0
+// and writes are ignored
+pub fn nvram_read(offset: u8) -> u8 {
+    if (NVRAM_START..=NVRAM_END).contains(&offset) {
+        read_register(offset)
+    } else {
+        0
+    }
+}
+
+pub fn nvram_write(offset: u8, value: u8) {
+    if (NVRAM_START..=NVRAM_END).contains(&offset) {
+        write_register(offset, value);
+    }
+}
+
+fn bcd_to_binary(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0f)
+}
+
+// Raw hours, minutes and seconds, read between two clock updates
+fn read_time_registers() -> (u8, u8, u8) {
+    while read_register(RTC_STATUS_A) & UPDATE_IN_PROGRESS != 0 {
+        core::hint::spin_loop();
+    }
+    (read_register(RTC_HOURS), read_register(RTC_MINUTES), read_register(RTC_SECONDS))
+}
+
+// Wall-clock time as (hours, minutes, seconds), hours 0-23. It's whatever the BIOS keeps,
+// usually local time.
+//
+// The RTC updates once a second, and registers read while it does can mix the old and
+// the new time, so reads repeat until two in a row agree. Status B says how the values
+// are stored: BCD or binary, and 12- or 24-hour. In 12-hour mode the PM flag sits in bit
+// 7 of the hours on top of either encoding, and midnight and noon are both hour 12.
+pub fn read_rtc() -> (u8, u8, u8) {
+    let mut time = read_time_registers();
+    loop {
+        let again = read_time_registers();
+        if again == time {
+            break;
+        }
+        time = again;
+    }
+    let (hours, minutes, seconds) = time;
+
+    let status_b = read_register(RTC_STATUS_B);
+    let decode = |value| if status_b & STATUS_B_BINARY != 0 { value } else { bcd_to_binary(value) };
+    let mut hours_24 = decode(hours & !HOUR_PM);
+    if status_b & STATUS_B_24_HOUR == 0 {
+        hours_24 %= 12;
+        if hours & HOUR_PM != 0 {
+            hours_24 += 12;
+        }
+    }
+    (hours_24, decode(minutes), decode(seconds))
+}
@@ -0,0 +1,121 @@
+// Teletype-style text output with print!/println!, so debug output doesn't need a (row, col)
+
+use core::fmt;
+
+use crate::spinlock::{SpinLock, SpinLockGuard};
+use crate::vga::{self, screen_cols, screen_rows};
+use crate::{ColorCode, LIGHT_GRAY, Vga, clear_screen, fill_region, write_char_at};
+
+const TAB_WIDTH: usize = 8;
+
+// Writes above the marquee row, scrolling up when it runs off the bottom. The hardware
+// cursor follows along, though it only shows while `read_line` waits for typing.
+pub struct Console {
+    pub row: usize,
+    pub col: usize,
+    pub color: ColorCode,
+}
+
+impl Console {
+    pub const fn new() -> Self {
+        Self { row: 0, col: 0, color: LIGHT_GRAY }
+    }
+
+    // Last row the console owns; the one below it belongs to the marquee
+    fn bottom() -> usize {
+        screen_rows() - 2
+    }
+
+    pub fn clear(&mut self) {
+        clear_screen();
+        self.row = 0;
+        self.col = 0;
+        self.sync_cursor();
+    }
+
+    fn scroll(&mut self) {
+        let mut screen = Vga::screen();
+        let cells = screen.cells();
+        for i in 0..Self::bottom() * 80 {
+            let below = cells[i + 80].read();
+            cells[i].write(below);
+        }
+        fill_region(Self::bottom(), 0, 80, 1, b' ', LIGHT_GRAY);
+    }
+
+    pub fn newline(&mut self) {
+        self.col = 0;
+        if self.row < Self::bottom() {
+            self.row += 1;
+        } else {
+            self.scroll();
+        }
+    }
+
+    // `\n` starts a new line, `\r` goes back to the start of this one, and `\t` pads
+    // with spaces to the next tab stop
+    pub fn put_byte(&mut self, byte: u8) {
+        match byte {
+            b'\n' => self.newline(),
+            b'\r' => self.col = 0,
+            b'\t' => {
+                for _ in 0..TAB_WIDTH - self.col % TAB_WIDTH {
+                    self.put_byte(b' ');
+                }
+            }
+            _ => {
+                write_char_at(byte, self.row, self.col, self.color);
+                self.col += 1;
+                if self.col == screen_cols() {
+                    self.newline();
+                }
+            }
+        }
+    }
+
+    pub fn put_bytes(&mut self, bytes: &[u8], color: ColorCode) {
+        self.color = color;
+        for &byte in bytes {
+            self.put_byte(byte);
+        }
+        self.sync_cursor();
+    }
+
+    fn sync_cursor(&self) {
+        vga::set_cursor(self.row, self.col);
+    }
+}
+
+impl fmt::Write for Console {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &byte in s.as_bytes() {
+            self.put_byte(byte);
+        }
+        self.sync_cursor();
+        Ok(())
+    }
+}
+
+// The console `print!` writes to, shared with the shell
+static CONSOLE: SpinLock<Console> = SpinLock::new(Console::new());
+
+// Interrupts are off while the console is held, so a handler that prints can't move the
+// cursor mid-line. Don't `print!` while holding it, that would spin forever.
+pub fn console() -> SpinLockGuard<'static, Console> {
+    CONSOLE.lock()
+}
+
+pub fn _print(args: fmt::Arguments) {
+    let _ = fmt::Write::write_fmt(&mut *console(), args);
+}
+
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => { $crate::console::_print(format_args!($($arg)*)) };
+}
+
+#[macro_export]
+macro_rules! println {
+    () => { $crate::print!("\n") };
+    ($($arg:tt)*) => { $crate::print!("{}\n", format_args!($($arg)*)) };
+}
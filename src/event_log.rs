@@ -0,0 +1,51 @@
+// Recent scheduler and input events, kept for the on-screen event viewer
+
+use crate::interrupts::without_interrupts;
+use crate::keyboard::Key;
+use crate::ring_buffer::RingBuffer;
+use crate::timer;
+
+#[derive(Clone, Copy)]
+pub enum Event {
+    KeyPressed(Key),
+    TaskSpawned(usize),
+    TaskFinished(usize),
+    TaskCancelled(usize),
+    SpawnFailed,
+}
+
+#[derive(Clone, Copy)]
+pub struct Entry {
+    pub tick: u64,
+    pub event: Event,
+}
+
+pub const CAPACITY: usize = 32;
+
+static LOG: RingBuffer<Entry, CAPACITY> = RingBuffer::new();
+
+// Append an event, dropping the oldest one when the log is full. Recording both pushes
+// and pops, so it has to stay out of interrupt handlers.
+pub fn record(event: Event) {
+    let entry = Entry { tick: timer::ticks(), event };
+    without_interrupts(|| {
+        if LOG.is_full() {
+            LOG.pop();
+        }
+        let _ = LOG.push(entry);
+    });
+}
+
+// Copy out the newest entries, oldest first. Returns how many were written to `out`.
+pub fn latest(out: &mut [Entry]) -> usize {
+    without_interrupts(|| {
+        let len = LOG.len();
+        let count = len.min(out.len());
+        for (i, slot) in out[..count].iter_mut().enumerate() {
+            if let Some(entry) = LOG.peek(len - count + i) {
+                *slot = entry;
+            }
+        }
+        count
+    })
+}
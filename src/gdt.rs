@@ -0,0 +1,106 @@
+// Global Descriptor Table with a Task State Segment, so faults can switch to a known-good stack
+
+use core::arch::asm;
+use core::mem::size_of;
+
+// IST slots are numbered 1-7 by the CPU; index 0 here is IST1
+pub const DOUBLE_FAULT_IST_INDEX: u16 = 0;
+
+const DOUBLE_FAULT_STACK_SIZE: usize = 4096 * 5;
+
+const KERNEL_CODE: u64 = 0x00af_9a00_0000_ffff; // present, ring 0, executable, 64-bit
+const KERNEL_DATA: u64 = 0x00cf_9200_0000_ffff; // present, ring 0, writable
+
+const KERNEL_CODE_SELECTOR: u16 = 1 << 3;
+const KERNEL_DATA_SELECTOR: u16 = 2 << 3;
+const TSS_SELECTOR: u16 = 3 << 3;
+
+#[repr(C, packed)]
+pub struct DescriptorTablePointer {
+    pub limit: u16,
+    pub base: u64,
+}
+
+#[repr(C, packed(4))]
+struct TaskStateSegment {
+    reserved_1: u32,
+    privilege_stack_table: [u64; 3],
+    reserved_2: u64,
+    interrupt_stack_table: [u64; 7],
+    reserved_3: u64,
+    reserved_4: u16,
+    iomap_base: u16,
+}
+
+#[repr(align(16))]
+struct Stack([u8; DOUBLE_FAULT_STACK_SIZE]);
+
+static mut DOUBLE_FAULT_STACK: Stack = Stack([0; DOUBLE_FAULT_STACK_SIZE]);
+
+static mut TSS: TaskStateSegment = TaskStateSegment {
+    reserved_1: 0,
+    privilege_stack_table: [0; 3],
+    reserved_2: 0,
+    interrupt_stack_table: [0; 7],
+    reserved_3: 0,
+    reserved_4: 0,
+    iomap_base: size_of::<TaskStateSegment>() as u16, // no I/O permission bitmap
+};
+
+// null, kernel code, kernel data, TSS (a system descriptor takes two slots)
+static mut GDT: [u64; 5] = [0; 5];
+
+// 64-bit available-TSS descriptor, split into its low and high halves
+fn tss_descriptor(base: u64) -> (u64, u64) {
+    let limit = (size_of::<TaskStateSegment>() - 1) as u64;
+    let low = (limit & 0xffff)
+        | ((base & 0x00ff_ffff) << 16)
+        | (0x89 << 40) // present, type 9 (available 64-bit TSS)
+        | (((limit >> 16) & 0xf) << 48)
+        | (((base >> 24) & 0xff) << 56);
+    (low, base >> 32)
+}
+
+// Replace the bootloader's GDT with ours and load the TSS. Must run before the IDT is built,
+// since IDT entries capture the current code segment.
+pub fn init() {
+    unsafe {
+        // Stacks grow down, so the IST entry points at the end of the buffer
+        let stack_start = core::ptr::addr_of!(DOUBLE_FAULT_STACK.0) as u64;
+        let tss = &mut *core::ptr::addr_of_mut!(TSS);
+        tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] = stack_start + DOUBLE_FAULT_STACK_SIZE as u64;
+
+        let gdt = &mut *core::ptr::addr_of_mut!(GDT);
+        let (tss_low, tss_high) = tss_descriptor(core::ptr::addr_of!(TSS) as u64);
+        gdt[1] = KERNEL_CODE;
+        gdt[2] = KERNEL_DATA;
+        gdt[3] = tss_low;
+        gdt[4] = tss_high;
+
+        let ptr = DescriptorTablePointer {
+            limit: (size_of::<[u64; 5]>() - 1) as u16,
+            base: gdt.as_ptr() as u64,
+        };
+        asm!("lgdt [{}]", in(reg) &ptr, options(readonly, nostack, preserves_flags));
+
+        // CS can only be reloaded with a far return
+        asm!(
+            "push {sel}",
+            "lea {tmp}, [rip + 2f]",
+            "push {tmp}",
+            "retfq",
+            "2:",
+            sel = in(reg) KERNEL_CODE_SELECTOR as u64,
+            tmp = lateout(reg) _,
+            options(preserves_flags),
+        );
+        asm!(
+            "mov ds, {0:x}",
+            "mov es, {0:x}",
+            "mov ss, {0:x}",
+            in(reg) KERNEL_DATA_SELECTOR,
+            options(nostack, preserves_flags),
+        );
+        asm!("ltr {0:x}", in(reg) TSS_SELECTOR, options(nostack, preserves_flags));
+    }
+}
@@ -0,0 +1,174 @@
+// Executor that boxes each task on the heap, so there's no cap on how many tasks run or on
+// how big their futures get. Built with the `heap-executor` feature, in place of the
+// fixed-slot `Executor`; it shares the same stop, pause and wakeup flags.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::Ordering;
+use core::task::{Context, Poll};
+
+use crate::event_log::{self, Event};
+use crate::{
+    CURRENT_SLOT, FOREGROUND_WAIT, MAX_SLOTS, MEMTEST_REGION, PAUSED, Priority, STOP_REQUESTS, SpawnError, TASK_READY, TaskId,
+    executor_waker, foreground_cancel_requested, foreground_cancelled, foreground_stop_requested, halt_until_ready, menu, runnable_tasks,
+    set_task_pinned, start_foreground_wait, warn_spawn_failed, watchdog,
+};
+
+struct HeapTask {
+    future: Pin<Box<dyn Future<Output = ()>>>,
+    pinned: bool, // keeps running while the executor is paused
+    type_name: &'static str,
+    size: usize,
+    id: TaskId,
+    priority: Priority,
+}
+
+pub struct HeapExecutor {
+    tasks: Vec<Option<HeapTask>>, // finished tasks leave a hole that the next spawn reuses
+    next_id: usize,
+}
+
+// Stop requests and ready flags have one bit per slot, so slots MAX_SLOTS apart share a
+// bit. Sharing only costs the odd extra poll.
+fn slot_bit(slot: usize) -> u32 {
+    1 << (slot % MAX_SLOTS)
+}
+
+impl HeapExecutor {
+    pub fn new() -> Self {
+        Self { tasks: Vec::new(), next_id: 0 }
+    }
+
+    // Never runs out of slots; returns a `Result` to match `Executor::spawn`
+    pub fn spawn<F: Future<Output = ()> + 'static>(&mut self, future: F) -> Result<TaskId, SpawnError> {
+        self.spawn_task(future, false, Priority::Normal)
+    }
+
+    pub fn spawn_background<F: Future<Output = ()> + 'static>(&mut self, future: F) -> Result<TaskId, SpawnError> {
+        self.spawn_task(future, true, Priority::Low)
+    }
+
+    fn spawn_task<F: Future<Output = ()> + 'static>(&mut self, future: F, pinned: bool, priority: Priority) -> Result<TaskId, SpawnError> {
+        let id = TaskId(self.next_id);
+        self.next_id += 1;
+        let task = HeapTask {
+            future: Box::pin(future),
+            pinned,
+            type_name: core::any::type_name::<F>(),
+            size: core::mem::size_of::<F>(),
+            id,
+            priority,
+        };
+        let slot = match self.tasks.iter().position(Option::is_none) {
+            Some(slot) => slot,
+            None => {
+                self.tasks.push(None);
+                self.tasks.len() - 1
+            }
+        };
+        STOP_REQUESTS.fetch_and(!slot_bit(slot), Ordering::Relaxed);
+        set_task_pinned(slot % MAX_SLOTS, pinned);
+        TASK_READY.fetch_or(slot_bit(slot), Ordering::Relaxed);
+        self.tasks[slot] = Some(task);
+        event_log::record(Event::TaskSpawned(slot));
+        Ok(id)
+    }
+
+    pub fn is_finished(&self, id: TaskId) -> bool {
+        !self.tasks.iter().flatten().any(|task| task.id == id)
+    }
+
+    // Same as `Executor::request_stop`
+    pub fn request_stop(&self, id: TaskId) {
+        let Some(slot) = self.tasks.iter().position(|task| task.as_ref().is_some_and(|task| task.id == id)) else {
+            return;
+        };
+        STOP_REQUESTS.fetch_or(slot_bit(slot), Ordering::Relaxed);
+        TASK_READY.fetch_or(slot_bit(slot), Ordering::Relaxed);
+    }
+
+    // Same as `Executor::cancel`: dropping the box drops the future
+    pub fn cancel(&mut self, id: TaskId) {
+        let Some(slot) = self.tasks.iter().position(|task| task.as_ref().is_some_and(|task| task.id == id)) else {
+            return;
+        };
+        self.tasks[slot] = None;
+        event_log::record(Event::TaskCancelled(slot));
+    }
+
+    // Poll `slot` if it has `priority`, its bit is in `ready` and it isn't paused
+    fn poll_slot(&mut self, slot: usize, ready: u32, priority: Priority) {
+        let Some(task) = &mut self.tasks[slot] else {
+            return;
+        };
+        if task.priority != priority || ready & slot_bit(slot) == 0 || (!task.pinned && PAUSED.load(Ordering::Relaxed)) {
+            return;
+        }
+        // `should_stop` looks at the bit for this slot
+        CURRENT_SLOT.store(slot % MAX_SLOTS, Ordering::Relaxed);
+        let waker = executor_waker(slot % MAX_SLOTS);
+        let mut context = Context::from_waker(&waker);
+        if let Poll::Ready(()) = task.future.as_mut().poll(&mut context) {
+            self.tasks[slot] = None;
+            event_log::record(Event::TaskFinished(slot));
+        }
+    }
+
+    // Log how big each running task's future is
+    pub fn dump_slot_sizes(&self) {
+        for (slot, task) in self.tasks.iter().enumerate() {
+            if let Some(task) = task {
+                log_info!("slot {}: {} bytes on the heap - {}", slot, task.size, task.type_name);
+            }
+        }
+    }
+
+    fn run_pass(&mut self) {
+        // Ready bits are taken for the whole pass, so every task sharing a bit gets
+        // polled and none of them loses its wakeup. Every ready task gets its poll; the
+        // low-priority ones just go last.
+        let ready = runnable_tasks();
+        TASK_READY.fetch_and(!ready, Ordering::Relaxed);
+        for priority in [Priority::Normal, Priority::Low] {
+            for slot in 0..self.tasks.len() {
+                self.poll_slot(slot, ready, priority);
+            }
+        }
+    }
+
+    // Same as `Executor::run_until`
+    pub fn run_until(&mut self, id: TaskId) {
+        start_foreground_wait();
+        loop {
+            watchdog::watchdog_pet();
+            if foreground_stop_requested() {
+                self.request_stop(id);
+            }
+            if foreground_cancel_requested() {
+                self.cancel(id);
+            }
+            self.run_pass();
+            if self.is_finished(id) {
+                break;
+            }
+            halt_until_ready();
+        }
+        FOREGROUND_WAIT.store(false, Ordering::Relaxed);
+    }
+
+    // Same as `Executor::run`
+    pub fn run(&mut self) -> ! {
+        loop {
+            match self.spawn(menu(*MEMTEST_REGION.lock())) {
+                Ok(menu) => {
+                    self.dump_slot_sizes();
+                    self.run_until(menu);
+                    foreground_cancelled();
+                }
+                Err(error) => warn_spawn_failed("menu", error),
+            }
+        }
+    }
+}
@@ -0,0 +1,365 @@
+// Interrupt Descriptor Table and hardware interrupt handlers
+
+use core::arch::asm;
+
+use crate::gdt::{self, DescriptorTablePointer};
+use crate::keyboard;
+use crate::mouse;
+use crate::pic;
+
+// What the CPU pushes before entering a handler
+#[derive(Debug)]
+#[repr(C)]
+pub struct InterruptStackFrame {
+    pub instruction_pointer: u64,
+    pub code_segment: u64,
+    pub cpu_flags: u64,
+    pub stack_pointer: u64,
+    pub stack_segment: u64,
+}
+
+type HandlerFn = extern "x86-interrupt" fn(InterruptStackFrame);
+type HandlerWithErrCodeFn = extern "x86-interrupt" fn(InterruptStackFrame, u64);
+type DivergingHandlerWithErrCodeFn = extern "x86-interrupt" fn(InterruptStackFrame, u64) -> !;
+
+const BREAKPOINT_VECTOR: u8 = 3;
+const DOUBLE_FAULT_VECTOR: u8 = 8;
+const GENERAL_PROTECTION_VECTOR: u8 = 13;
+const PAGE_FAULT_VECTOR: u8 = 14;
+
+// Page fault error code bits
+pub const PF_PRESENT: u64 = 1 << 0; // set: protection violation, clear: page not mapped
+pub const PF_WRITE: u64 = 1 << 1;
+pub const PF_USER: u64 = 1 << 2;
+pub const PF_RESERVED: u64 = 1 << 3; // a reserved bit was set in a page table entry
+pub const PF_INSTRUCTION: u64 = 1 << 4;
+
+// General protection fault error code: a segment selector index plus where it points
+pub const GP_EXTERNAL: u64 = 1 << 0;
+pub const GP_TABLE_SHIFT: u64 = 1; // 0 = GDT, 1 or 3 = IDT, 2 = LDT
+pub const GP_INDEX_SHIFT: u64 = 3;
+
+// CPU exceptions by vector; the ones marked reserved are never raised
+const EXCEPTION_NAMES: [&str; 32] = [
+    "DIVIDE ERROR",
+    "DEBUG",
+    "NON-MASKABLE INTERRUPT",
+    "BREAKPOINT",
+    "OVERFLOW",
+    "BOUND RANGE EXCEEDED",
+    "INVALID OPCODE",
+    "DEVICE NOT AVAILABLE",
+    "DOUBLE FAULT",
+    "COPROCESSOR SEGMENT OVERRUN",
+    "INVALID TSS",
+    "SEGMENT NOT PRESENT",
+    "STACK-SEGMENT FAULT",
+    "GENERAL PROTECTION FAULT",
+    "PAGE FAULT",
+    "RESERVED",
+    "X87 FLOATING-POINT EXCEPTION",
+    "ALIGNMENT CHECK",
+    "MACHINE CHECK",
+    "SIMD FLOATING-POINT EXCEPTION",
+    "VIRTUALIZATION EXCEPTION",
+    "CONTROL PROTECTION EXCEPTION",
+    "RESERVED",
+    "RESERVED",
+    "RESERVED",
+    "RESERVED",
+    "RESERVED",
+    "RESERVED",
+    "HYPERVISOR INJECTION EXCEPTION",
+    "VMM COMMUNICATION EXCEPTION",
+    "SECURITY EXCEPTION",
+    "RESERVED",
+];
+
+pub fn exception_name(vector: u8) -> &'static str {
+    EXCEPTION_NAMES.get(vector as usize).copied().unwrap_or("NOT AN EXCEPTION")
+}
+
+// The exception behind the current panic, if it came from one
+#[derive(Clone, Copy)]
+#[allow(clippy::enum_variant_names)] // PageFault is what it's called
+pub enum Fault {
+    PageFault { address: u64, error_code: u64 },
+    GeneralProtection { error_code: u64 },
+    // Any other exception; only some of them push an error code
+    Other { vector: u8, error_code: Option<u64> },
+}
+
+// Only written by fault handlers right before they panic
+static mut LAST_FAULT: Option<Fault> = None;
+
+pub fn last_fault() -> Option<Fault> {
+    unsafe { *core::ptr::addr_of!(LAST_FAULT) }
+}
+
+pub const TIMER_VECTOR: u8 = pic::PIC1_OFFSET + pic::IRQ_TIMER;
+pub const KEYBOARD_VECTOR: u8 = pic::PIC1_OFFSET + pic::IRQ_KEYBOARD;
+pub const MOUSE_VECTOR: u8 = pic::PIC2_OFFSET + (pic::IRQ_MOUSE - 8);
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct IdtEntry {
+    offset_low: u16,
+    selector: u16,
+    options: u16, // IST index (bits 0-2), gate type (8-11), present (15)
+    offset_mid: u16,
+    offset_high: u32,
+    reserved: u32,
+}
+
+impl IdtEntry {
+    const fn missing() -> Self {
+        Self {
+            offset_low: 0,
+            selector: 0,
+            options: 0x0e00, // 64-bit interrupt gate, not present
+            offset_mid: 0,
+            offset_high: 0,
+            reserved: 0,
+        }
+    }
+
+    fn set_handler_addr(&mut self, addr: u64) {
+        let cs: u16;
+        unsafe { asm!("mov {0:x}, cs", out(reg) cs, options(nomem, nostack, preserves_flags)); }
+
+        self.offset_low = addr as u16;
+        self.offset_mid = (addr >> 16) as u16;
+        self.offset_high = (addr >> 32) as u32;
+        self.selector = cs;
+        self.options = 0x8e00; // present, ring 0, interrupt gate
+    }
+
+    // Switch to IST stack `index` (0-based) when this vector fires
+    fn set_stack_index(&mut self, index: u16) {
+        self.options = (self.options & !0b111) | (index + 1);
+    }
+}
+
+#[repr(C, align(16))]
+pub struct Idt([IdtEntry; 256]);
+
+impl Idt {
+    const fn new() -> Self {
+        Self([IdtEntry::missing(); 256])
+    }
+
+    fn set_handler(&mut self, vector: u8, handler: HandlerFn) {
+        self.0[vector as usize].set_handler_addr(handler as usize as u64);
+    }
+
+    fn set_handler_with_err_code(&mut self, vector: u8, handler: HandlerWithErrCodeFn) {
+        self.0[vector as usize].set_handler_addr(handler as usize as u64);
+    }
+
+    // Must be 'static: the CPU keeps using the table after `lidt`
+    fn load(&'static self) {
+        let ptr = DescriptorTablePointer {
+            limit: (core::mem::size_of::<Self>() - 1) as u16,
+            base: self as *const _ as u64,
+        };
+        unsafe { asm!("lidt [{}]", in(reg) &ptr, options(readonly, nostack, preserves_flags)); }
+    }
+}
+
+static mut IDT: Idt = Idt::new();
+
+// Build and load the IDT, then remap the PICs and unmask the IRQs we handle.
+// Interrupts stay disabled until `enable()`.
+// Call `gdt::init()` first so the double-fault IST stack is in place.
+pub fn init() {
+    unsafe {
+        let idt = &mut *core::ptr::addr_of_mut!(IDT);
+        let double_fault: DivergingHandlerWithErrCodeFn = double_fault_handler;
+        let entry = &mut idt.0[DOUBLE_FAULT_VECTOR as usize];
+        entry.set_handler_addr(double_fault as usize as u64);
+        entry.set_stack_index(gdt::DOUBLE_FAULT_IST_INDEX);
+        idt.set_handler_with_err_code(GENERAL_PROTECTION_VECTOR, general_protection_handler);
+        idt.set_handler_with_err_code(PAGE_FAULT_VECTOR, page_fault_handler);
+        idt.set_handler(BREAKPOINT_VECTOR, breakpoint_handler);
+        set_exception_handlers(idt);
+        idt.set_handler(TIMER_VECTOR, timer_interrupt_handler);
+        idt.set_handler(KEYBOARD_VECTOR, keyboard_interrupt_handler);
+        idt.set_handler(MOUSE_VECTOR, mouse_interrupt_handler);
+        idt.load();
+    }
+    pic::remap(pic::PIC1_OFFSET, pic::PIC2_OFFSET);
+    // `remap` leaves every line masked. Only IRQs with a handler above get unmasked: an
+    // IRQ arriving on a vector with no IDT entry would fault. The mouse sits on the
+    // slave PIC, so unmasking it opens the IRQ2 cascade as well.
+    pic::set_mask(pic::IRQ_TIMER, false);
+    pic::set_mask(pic::IRQ_KEYBOARD, false);
+    pic::set_mask(pic::IRQ_MOUSE, false);
+}
+
+pub fn enable() {
+    unsafe { asm!("sti", options(nomem, nostack)); }
+}
+
+pub fn disable() {
+    unsafe { asm!("cli", options(nomem, nostack)); }
+}
+
+// Is the interrupt flag (IF) set?
+pub fn are_enabled() -> bool {
+    let rflags: u64;
+    unsafe { asm!("pushfq", "pop {}", out(reg) rflags, options(nomem, preserves_flags)); }
+    rflags & (1 << 9) != 0
+}
+
+// Run `f` with interrupts disabled, then put the interrupt flag back the way it was.
+// Restoring (rather than always `sti`) keeps nested critical sections correct.
+pub fn without_interrupts<F: FnOnce() -> R, R>(f: F) -> R {
+    let were_enabled = are_enabled();
+    
+    if were_enabled {
+        disable();
+    }
+    let result = f();
+    if were_enabled {
+        enable();
+    }
+    result
+}
+
+// `sti` only takes effect after the next instruction, so nothing can fire between the two
+pub fn enable_and_halt() {
+    unsafe { asm!("sti; hlt", options(nomem, nostack)); }
+}
+
+// Sleep until the next interrupt (timer, keyboard, ...)
+pub fn halt() {
+    unsafe { asm!("hlt", options(nomem, nostack, preserves_flags)); }
+}
+
+// Park the CPU for good without spinning at 100%
+pub fn hlt_loop() -> ! {
+    loop {
+        halt();
+    }
+}
+
+extern "x86-interrupt" fn timer_interrupt_handler(_frame: InterruptStackFrame) {
+    crate::timer::on_tick();
+    pic::send_eoi(pic::IRQ_TIMER);
+    crate::watchdog::check(); // may panic, so only after the EOI
+}
+
+// Queues the scancode for `keyboard::poll_key`. ESC is the global "back to the menu" key,
+// so it also asks the foreground task to stop.
+extern "x86-interrupt" fn keyboard_interrupt_handler(_frame: InterruptStackFrame) {
+    match keyboard::handle_interrupt() {
+        keyboard::SCANCODE_ESC => crate::DefaultExecutor::stop_key(),
+        // On release, so the keyboard's own repeat can't toggle it back and forth
+        code if code == keyboard::SCANCODE_F11 | keyboard::RELEASE_BIT => crate::DefaultExecutor::toggle_paused(),
+        code if code == keyboard::SCANCODE_F12 | keyboard::RELEASE_BIT => crate::DefaultExecutor::panic_key(),
+        _ => {}
+    }
+    pic::send_eoi(pic::IRQ_KEYBOARD);
+}
+
+extern "x86-interrupt" fn mouse_interrupt_handler(_frame: InterruptStackFrame) {
+    mouse::handle_interrupt();
+    pic::send_eoi(pic::IRQ_MOUSE);
+}
+
+// Runs on its own IST stack, so even a blown kernel stack ends on the panic screen
+extern "x86-interrupt" fn double_fault_handler(frame: InterruptStackFrame, _error_code: u64) -> ! {
+    panic!("DOUBLE FAULT - swag stack overflow? rip={:#x} rsp={:#x}", frame.instruction_pointer, frame.stack_pointer);
+}
+
+// Record `fault` for the panic screen and panic with where the CPU was. The panic
+// handler puts the message on screen and on COM1.
+fn fault(vector: u8, frame: &InterruptStackFrame, fault: Fault) -> ! {
+    unsafe { *core::ptr::addr_of_mut!(LAST_FAULT) = Some(fault) };
+    panic!(
+        "{} at rip={:#x} cs={:#x} rflags={:#x}",
+        exception_name(vector),
+        frame.instruction_pointer,
+        frame.code_segment,
+        frame.cpu_flags
+    );
+}
+
+extern "x86-interrupt" fn general_protection_handler(frame: InterruptStackFrame, error_code: u64) {
+    fault(GENERAL_PROTECTION_VECTOR, &frame, Fault::GeneralProtection { error_code });
+}
+
+// CR2 holds the address the CPU was trying to reach
+extern "x86-interrupt" fn page_fault_handler(frame: InterruptStackFrame, error_code: u64) {
+    let address: u64;
+    unsafe { asm!("mov {}, cr2", out(reg) address, options(nomem, nostack, preserves_flags)); }
+    fault(PAGE_FAULT_VECTOR, &frame, Fault::PageFault { address, error_code });
+}
+
+// `int3` is meant to be carried on from, so a breakpoint only gets logged
+extern "x86-interrupt" fn breakpoint_handler(frame: InterruptStackFrame) {
+    log_warn!("breakpoint at rip={:#x}", frame.instruction_pointer);
+}
+
+// One handler per vector, since nothing tells a handler which vector it's running for,
+// plus `set_exception_handlers` to put them all in the IDT. Vectors listed with
+// `error_code` are the ones the CPU pushes an error code for.
+macro_rules! exception_handlers {
+    ($($vector:literal $handler:ident $($error_code:ident)?;)*) => {
+        $(exception_handlers!(@handler $vector $handler $($error_code)?);)*
+
+        fn set_exception_handlers(idt: &mut Idt) {
+            $(exception_handlers!(@register idt $vector $handler $($error_code)?);)*
+        }
+    };
+    (@handler $vector:literal $handler:ident) => {
+        extern "x86-interrupt" fn $handler(frame: InterruptStackFrame) {
+            fault($vector, &frame, Fault::Other { vector: $vector, error_code: None });
+        }
+    };
+    (@handler $vector:literal $handler:ident $error_code:ident) => {
+        extern "x86-interrupt" fn $handler(frame: InterruptStackFrame, $error_code: u64) {
+            fault($vector, &frame, Fault::Other { vector: $vector, error_code: Some($error_code) });
+        }
+    };
+    (@register $idt:ident $vector:literal $handler:ident) => {
+        $idt.set_handler($vector, $handler)
+    };
+    (@register $idt:ident $vector:literal $handler:ident $error_code:ident) => {
+        $idt.set_handler_with_err_code($vector, $handler)
+    };
+}
+
+// Everything but breakpoints, double faults, GP faults and page faults, which have their
+// own handlers above. The CPU never raises the reserved vectors, but an `int n` still can,
+// and an empty gate would only turn that into a #NP that names the wrong vector.
+exception_handlers! {
+    0 divide_error_handler;
+    1 debug_handler;
+    2 nmi_handler;
+    4 overflow_handler;
+    5 bound_range_handler;
+    6 invalid_opcode_handler;
+    7 device_not_available_handler;
+    9 coprocessor_segment_overrun_handler;
+    10 invalid_tss_handler error_code;
+    11 segment_not_present_handler error_code;
+    12 stack_segment_handler error_code;
+    15 reserved_15_handler;
+    16 x87_floating_point_handler;
+    17 alignment_check_handler error_code;
+    18 machine_check_handler;
+    19 simd_floating_point_handler;
+    20 virtualization_handler;
+    21 control_protection_handler error_code;
+    22 reserved_22_handler;
+    23 reserved_23_handler;
+    24 reserved_24_handler;
+    25 reserved_25_handler;
+    26 reserved_26_handler;
+    27 reserved_27_handler;
+    28 hypervisor_injection_handler;
+    29 vmm_communication_handler error_code;
+    30 security_exception_handler error_code;
+    31 reserved_31_handler;
+}
@@ -0,0 +1,259 @@
+// PS/2 keyboard input with software key repeat
+
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, Ordering};
+use core::task::{Context, Poll, Waker};
+
+use crate::event_log::{self, Event};
+use crate::interrupts::without_interrupts;
+use crate::port::{inb, outb};
+use crate::ring_buffer::RingBuffer;
+use crate::timer::{self, ticks};
+
+const DATA_PORT: u16 = 0x60;
+const STATUS_PORT: u16 = 0x64; // reads status, writes controller commands
+
+const STATUS_INPUT_FULL: u8 = 0x02;
+const CMD_PULSE_RESET: u8 = 0xfe;
+
+// A key as reported by `poll_key`: its make code (scancode set 1)
+pub type Key = u8;
+
+pub const SCANCODE_ESC: u8 = 0x01;
+pub const SCANCODE_BACKSPACE: u8 = 0x0e;
+pub const SCANCODE_ENTER: u8 = 0x1c;
+pub const SCANCODE_F11: u8 = 0x57;
+pub const SCANCODE_F12: u8 = 0x58;
+pub const SCANCODE_W: u8 = 0x11;
+pub const SCANCODE_S: u8 = 0x1f;
+pub const SCANCODE_UP: u8 = 0x48; // keypad 8 too, the arrows only add an 0xe0 prefix
+pub const SCANCODE_DOWN: u8 = 0x50;
+
+const SCANCODE_LEFT_SHIFT: u8 = 0x2a;
+const SCANCODE_RIGHT_SHIFT: u8 = 0x36;
+const SCANCODE_CAPS_LOCK: u8 = 0x3a;
+
+// US layout indexed by make code, without and with Shift; 0 where a key doesn't type anything
+const ASCII: &[u8; 0x3a] = b"\x00\x001234567890-=\x00\x00qwertyuiop[]\x00\x00asdfghjkl;'`\x00\\zxcvbnm,./\x00*\x00 ";
+const ASCII_SHIFTED: &[u8; 0x3a] = b"\x00\x00!@#$%^&*()_+\x00\x00QWERTYUIOP{}\x00\x00ASDFGHJKL:\"~\x00|ZXCVBNM<>?\x00*\x00 ";
+
+pub const RELEASE_BIT: u8 = 0x80;
+const EXTENDED_PREFIX: u8 = 0xe0;
+const NO_KEY: u8 = 0;
+
+// Defaults in timer ticks (100 Hz): half a second before repeating, then ~33 repeats/s
+const DEFAULT_REPEAT_DELAY: u64 = 50;
+const DEFAULT_REPEAT_RATE: u64 = 3;
+
+static REPEAT_DELAY: AtomicU64 = AtomicU64::new(DEFAULT_REPEAT_DELAY);
+static REPEAT_RATE: AtomicU64 = AtomicU64::new(DEFAULT_REPEAT_RATE);
+
+// Key currently held down and when it repeats next
+static HELD_KEY: AtomicU8 = AtomicU8::new(NO_KEY);
+static NEXT_REPEAT: AtomicU64 = AtomicU64::new(0);
+
+// Tick of the last key `poll_key` handed out, for idle detection
+static LAST_KEY_TICK: AtomicU64 = AtomicU64::new(0);
+
+// Configure auto-repeat for held keys: first repeat after `initial_ticks`, then every `rate_ticks`
+pub fn set_repeat(initial_ticks: u64, rate_ticks: u64) {
+    REPEAT_DELAY.store(initial_ticks, Ordering::Relaxed);
+    REPEAT_RATE.store(rate_ticks.max(1), Ordering::Relaxed);
+}
+
+// Which keys are held right now, by make code, as the IRQ1 handler last saw them. Games
+// read this every frame instead of waiting for repeats, and it sees several keys at once.
+static KEY_STATE: [AtomicBool; 128] = [const { AtomicBool::new(false) }; 128];
+
+// Scancodes read by the IRQ1 handler, waiting for `poll_key`. The handler is the only
+// producer; it also pops, but only when the queue is full, and every other pop happens
+// in `read_scancode` with interrupts off.
+static QUEUE: RingBuffer<u8, 32> = RingBuffer::new();
+
+// Modifier keys as seen by `poll_key`. Modifiers aren't handed out as key presses;
+// they only change what the other keys type.
+#[derive(Clone, Copy)]
+pub struct KeyboardState {
+    left_shift: bool,
+    right_shift: bool,
+    caps_lock: bool,
+}
+
+impl KeyboardState {
+    const fn new() -> Self {
+        Self { left_shift: false, right_shift: false, caps_lock: false }
+    }
+
+    // Track a modifier press or release; false if `code` isn't a modifier
+    fn update(&mut self, code: u8) -> bool {
+        match code {
+            SCANCODE_LEFT_SHIFT => self.left_shift = true,
+            SCANCODE_RIGHT_SHIFT => self.right_shift = true,
+            SCANCODE_CAPS_LOCK => self.caps_lock = !self.caps_lock,
+            c if c == SCANCODE_LEFT_SHIFT | RELEASE_BIT => self.left_shift = false,
+            c if c == SCANCODE_RIGHT_SHIFT | RELEASE_BIT => self.right_shift = false,
+            c if c == SCANCODE_CAPS_LOCK | RELEASE_BIT => {}
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn shift(&self) -> bool {
+        self.left_shift || self.right_shift
+    }
+
+    // The character `key` types with these modifiers. Caps Lock only flips letters.
+    pub fn decode(&self, key: Key) -> Option<char> {
+        let letter = decode_scancode(key, false).is_some_and(|ch| ch.is_ascii_lowercase());
+        decode_scancode(key, self.shift() ^ (letter && self.caps_lock))
+    }
+}
+
+// Only touched from `poll_key` and friends, never from the interrupt handler
+static mut STATE: KeyboardState = KeyboardState::new();
+
+fn state() -> &'static mut KeyboardState {
+    unsafe { &mut *core::ptr::addr_of_mut!(STATE) }
+}
+
+// Task waiting in `KeyFuture`, woken by the next scancode. There's only one consumer.
+static mut KEY_WAKER: Option<Waker> = None;
+
+// Called from the keyboard interrupt (so already with interrupts off): read the scancode
+// and queue it. Returns the scancode.
+pub fn handle_interrupt() -> u8 {
+    let code = unsafe { inb(DATA_PORT) };
+    if code != EXTENDED_PREFIX {
+        KEY_STATE[(code & !RELEASE_BIT) as usize].store(code & RELEASE_BIT == 0, Ordering::Relaxed);
+    }
+    if let Err(code) = QUEUE.push(code) {
+        // Nobody has read input in a while; what was just typed matters more than the
+        // oldest scancode, so that one makes room
+        let _ = QUEUE.pop();
+        let _ = QUEUE.push(code);
+    }
+    if let Some(waker) = unsafe { (*core::ptr::addr_of_mut!(KEY_WAKER)).take() } {
+        waker.wake();
+    }
+    code
+}
+
+// Wake `waker` on the next scancode, or when a held key is due to repeat
+fn register_waker(waker: &Waker) {
+    without_interrupts(|| unsafe { *core::ptr::addr_of_mut!(KEY_WAKER) = Some(waker.clone()) });
+    if HELD_KEY.load(Ordering::Relaxed) != NO_KEY {
+        timer::wake_at(NEXT_REPEAT.load(Ordering::Relaxed), waker);
+    }
+}
+
+// The IRQ handler can't interrupt a pop halfway through
+fn read_scancode() -> Option<u8> {
+    without_interrupts(|| QUEUE.pop())
+}
+
+// Throw away queued input and forget any held key. Shift releases may have been thrown
+// away too, so Shift counts as up again; Caps Lock stays as it was. Every key counts as
+// up in `is_key_down` until it's pressed again, so nothing held stays stuck.
+pub fn flush() {
+    while read_scancode().is_some() {}
+    HELD_KEY.store(NO_KEY, Ordering::Relaxed);
+    for key in &KEY_STATE {
+        key.store(false, Ordering::Relaxed);
+    }
+    let state = state();
+    state.left_shift = false;
+    state.right_shift = false;
+}
+
+// Next key press (make code), including our own repeats for a held key.
+// Releases and the keyboard's built-in typematic repeats are swallowed here.
+pub fn poll_key() -> Option<Key> {
+    while let Some(code) = read_scancode() {
+        if code == EXTENDED_PREFIX || state().update(code) {
+            continue;
+        }
+
+        if code & RELEASE_BIT != 0 {
+            // Releasing the held key cancels the repeat right away
+            let _ = HELD_KEY.compare_exchange(code & !RELEASE_BIT, NO_KEY, Ordering::Relaxed, Ordering::Relaxed);
+            continue;
+        }
+
+        if code == HELD_KEY.load(Ordering::Relaxed) {
+            continue; // hardware repeat, we generate our own
+        }
+
+        let now = ticks();
+        HELD_KEY.store(code, Ordering::Relaxed);
+        NEXT_REPEAT.store(now + REPEAT_DELAY.load(Ordering::Relaxed), Ordering::Relaxed);
+        LAST_KEY_TICK.store(now, Ordering::Relaxed);
+        event_log::record(Event::KeyPressed(code));
+        return Some(code);
+    }
+
+    let held = HELD_KEY.load(Ordering::Relaxed);
+    let now = ticks();
+    if held != NO_KEY && now >= NEXT_REPEAT.load(Ordering::Relaxed) {
+        NEXT_REPEAT.store(now + REPEAT_RATE.load(Ordering::Relaxed), Ordering::Relaxed);
+        LAST_KEY_TICK.store(now, Ordering::Relaxed);
+        return Some(held);
+    }
+    None
+}
+
+// Whether the key with make code `code` is held down right now
+pub fn is_key_down(code: u8) -> bool {
+    KEY_STATE[(code & !RELEASE_BIT) as usize].load(Ordering::Relaxed)
+}
+
+// How long it's been since the last key press or repeat
+pub fn ticks_since_last_key() -> u64 {
+    ticks().saturating_sub(LAST_KEY_TICK.load(Ordering::Relaxed))
+}
+
+// Character a set 1 scancode types, if any. Releases and keys that don't type anything,
+// modifiers included, give `None`.
+pub fn decode_scancode(code: u8, shift: bool) -> Option<char> {
+    if code & RELEASE_BIT != 0 {
+        return None;
+    }
+    let table = if shift { ASCII_SHIFTED } else { ASCII };
+    table.get(code as usize).copied().filter(|&ch| ch != 0).map(char::from)
+}
+
+// What `key` types with the modifiers currently held
+pub fn typed_char(key: Key) -> Option<char> {
+    state().decode(key)
+}
+
+// Pulse the CPU reset line through the 8042 controller
+pub fn reboot() -> ! {
+    unsafe {
+        while inb(STATUS_PORT) & STATUS_INPUT_FULL != 0 {
+            core::hint::spin_loop();
+        }
+        outb(STATUS_PORT, CMD_PULSE_RESET);
+    }
+    crate::interrupts::hlt_loop()
+}
+
+pub struct KeyFuture;
+
+impl Future for KeyFuture {
+    type Output = u8;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Registered first, so a key arriving right after `poll_key` comes up empty still wakes us
+        register_waker(cx.waker());
+        match poll_key() {
+            Some(code) => Poll::Ready(code),
+            None => Poll::Pending,
+        }
+    }
+}
+
+// Wait for the next key press or repeat
+pub fn next_key() -> KeyFuture {
+    KeyFuture
+}
@@ -0,0 +1,73 @@
+// Maps physical keys to what they mean, so apps match on actions instead of scancodes
+
+use crate::keyboard::Key;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    MenuSelect(u8), // 1-based, as numbered on the menu
+    Back,
+    Up,
+    Down,
+    Left,
+    Right,
+    Faster,
+    Slower,
+    NextPalette,
+    // Hidden menu entries
+    StackOverflow,
+    EventLog,
+    Shell,
+    Bench,
+    Clock,
+}
+
+// One entry per make code in scancode set 1
+pub const KEYMAP_SIZE: usize = 0x80;
+
+pub type Keymap = [Option<Action>; KEYMAP_SIZE];
+
+// US layout: number row picks menu entries, arrows move, ESC goes back
+pub const US_LAYOUT: Keymap = {
+    let mut map: Keymap = [None; KEYMAP_SIZE];
+    map[0x01] = Some(Action::Back);
+    let mut n = 1;
+    while n <= 10 {
+        map[n + 1] = Some(Action::MenuSelect(n as u8)); // '1' is 0x02, '0' after '9' picks 10
+        n += 1;
+    }
+    map[0x0c] = Some(Action::Slower); // -
+    map[0x0d] = Some(Action::Faster); // the =/+ key
+    map[0x4a] = Some(Action::Slower); // keypad -
+    map[0x4e] = Some(Action::Faster); // keypad +
+    map[0x19] = Some(Action::NextPalette); // P
+    map[0x48] = Some(Action::Up);
+    map[0x50] = Some(Action::Down);
+    map[0x4b] = Some(Action::Left);
+    map[0x4d] = Some(Action::Right);
+    map[0x18] = Some(Action::StackOverflow); // O: exercises the double-fault handler
+    map[0x26] = Some(Action::EventLog); // L
+    map[0x1f] = Some(Action::Shell); // S
+    map[0x30] = Some(Action::Bench); // B
+    map[0x2e] = Some(Action::Clock); // C
+    map
+};
+
+// Only ever touched from tasks, never from interrupt handlers
+static mut KEYMAP: Keymap = US_LAYOUT;
+
+// What `key` does under the current keymap, if anything
+pub fn action(key: Key) -> Option<Action> {
+    unsafe { (*core::ptr::addr_of!(KEYMAP)).get(key as usize).copied().flatten() }
+}
+
+// Rebind a single key, e.g. `set_action(0x11, Action::Up)` for W
+pub fn set_action(key: Key, action: Action) {
+    if let Some(entry) = unsafe { (*core::ptr::addr_of_mut!(KEYMAP)).get_mut(key as usize) } {
+        *entry = Some(action);
+    }
+}
+
+// Swap in a whole different table
+pub fn set_keymap(map: &Keymap) {
+    unsafe { *core::ptr::addr_of_mut!(KEYMAP) = *map };
+}
@@ -0,0 +1,81 @@
+// Leveled logging to the serial port, filtered at runtime
+
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::interrupts::without_interrupts;
+use crate::serial::{self, SerialPort};
+use crate::timer;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl Level {
+    fn label(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN ",
+            Level::Info => "INFO ",
+            Level::Debug => "DEBUG",
+        }
+    }
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+// Messages less severe than `level` are dropped
+pub fn set_log_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+// The macros check this before building any formatting arguments
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+// One line: "[seconds.millis] LEVEL message". Interrupts are held off so a handler
+// that logs can't splice its line into the middle of ours.
+pub fn write(level: Level, args: fmt::Arguments) {
+    let ms = timer::uptime_ms();
+    without_interrupts(|| {
+        let mut serial = SerialPort(serial::COM1);
+        let _ = write!(serial, "[{:>5}.{:03}] {} ", ms / 1000, ms % 1000, level.label());
+        let _ = serial.write_fmt(args);
+        let _ = serial.write_str("\n");
+    });
+}
+
+#[macro_export]
+macro_rules! log_at {
+    ($level:expr, $($arg:tt)*) => {
+        if $crate::log::enabled($level) {
+            $crate::log::write($level, format_args!($($arg)*));
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! log_error {
+    ($($arg:tt)*) => { $crate::log_at!($crate::log::Level::Error, $($arg)*) };
+}
+
+#[macro_export]
+macro_rules! log_warn {
+    ($($arg:tt)*) => { $crate::log_at!($crate::log::Level::Warn, $($arg)*) };
+}
+
+#[macro_export]
+macro_rules! log_info {
+    ($($arg:tt)*) => { $crate::log_at!($crate::log::Level::Info, $($arg)*) };
+}
+
+#[macro_export]
+macro_rules! log_debug {
+    ($($arg:tt)*) => { $crate::log_at!($crate::log::Level::Debug, $($arg)*) };
+}
@@ -1,17 +1,78 @@
 #![no_std] // don't link the Rust standard library
 #![no_main] // disable all Rust-level entry points
+#![feature(abi_x86_interrupt)] // needed for `extern "x86-interrupt"` handlers
+#![feature(alloc_error_handler)] // lets the allocator panic its own way when the heap runs out
+#![feature(custom_test_frameworks)] // `#[test_case]` tests run in the kernel itself
+#![test_runner(crate::test_runner)]
+#![reexport_test_harness_main = "test_main"]
 
+extern crate alloc;
+
+#[macro_use]
+mod log;
+
+mod allocator;
+mod channel;
+mod cmos;
+#[macro_use]
+mod console;
+mod event_log;
+mod gdt;
+#[cfg(feature = "heap-executor")]
+mod heap_executor;
+mod interrupts;
+mod keyboard;
+mod keymap;
+mod mem;
+mod mouse;
+mod pic;
+mod port;
+mod qemu;
+mod ring_buffer;
+#[macro_use]
+mod serial;
+mod speaker;
+mod spinlock;
+mod timer;
+mod transition;
+mod vga;
+mod volatile;
+mod watchdog;
+
+use core::fmt::{self, Write};
 use core::panic::PanicInfo;
 use core::future::Future;
 use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
 use core::task::{Context, Poll, Waker};
 
-// Keyboard scan codes for number keys
-const KEY_1: u8 = 0x02;
-const KEY_2: u8 = 0x03;
-const KEY_3: u8 = 0x04;
-const KEY_4: u8 = 0x05; // NEW!
-const KEY_ESC: u8 = 0x01;
+use bootloader::bootinfo::{BootInfo, MemoryRegionType};
+use channel::Channel;
+use console::console;
+use event_log::Event;
+use keymap::Action;
+use serial::SerialPort;
+use spinlock::SpinLock;
+use timer::{FrameLimiter, Interval, Stopwatch, timeout};
+use vga::{screen_cols, screen_rows};
+use volatile::Volatile;
+
+// Frame rate for the matrix and hypnotizer animations
+const TARGET_FPS: u32 = 30;
+
+// The hypnotizer returns to the menu on its own after this long
+const HYPNOTIZER_TIMEOUT_MS: u64 = 60_000;
+
+// The menu starts the hypnotizer as a screensaver after this long without a key press
+const SCREENSAVER_TIMEOUT_MS: u64 = 30_000;
+
+// +/- in the hypnotizer steps the animation speed within this range; P cycles palettes
+const HYPNOTIZER_MAX_SPEED: i32 = 8;
+const HYPNOTIZER_PALETTES: [Palette; 3] = [RAINBOW, FIRE, MATRIX_GREEN];
+// +/- in the other apps step the global speed scale (Q8.8) by this much
+const SPEED_STEP: u32 = timer::SPEED_SCALE_ONE / 8;
+
+const HYPNOTIZER_BORDER_TRAIL: usize = 12; // cells in the snake running around the border
 
 // === ASYNC RUNTIME ===
 
@@ -19,48 +80,87 @@ const KEY_ESC: u8 = 0x01;
 type TaskPollFn = fn(*mut u8, &mut Context<'_>) -> Poll<()>;
 type TaskDropFn = fn(*mut u8);
 
-struct Task {
+#[cfg_attr(feature = "heap-executor", allow(dead_code))]
+struct Task<const S: usize> {
     poll_fn: Option<TaskPollFn>,
     drop_fn: Option<TaskDropFn>,
-    storage: [u8; 512], // Static storage for future state
+    storage: TaskStorage<S>, // Static storage for future state
+    size: usize, // Bytes of `storage` the current future actually needs
+    type_name: &'static str,
+    id: TaskId,
+    priority: Priority,
 }
 
-impl Task {
+// Bytes of storage in each task slot by default, which is the most a spawned future may
+// take up. A bigger one fails to build (see `task_storage_needed`); the fix is usually to
+// move large arrays out of the async fn into a static, like BACKBUFFER or PARTICLES.
+const TASK_STORAGE: usize = 512;
+// Alignment of the storage, and the most a spawned future may need
+const TASK_STORAGE_ALIGN: usize = 16;
+
+// The future is used in place, so its bytes must sit where an `F` may live. A plain
+// byte array would only be 1-aligned.
+#[repr(C, align(16))]
+struct TaskStorage<const S: usize>([u8; S]);
+
+impl<const S: usize> TaskStorage<S> {
+    fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.0.as_mut_ptr()
+    }
+}
+
+// How many bytes of an `S`-byte task slot a future of type `F` occupies. The assertions
+// run when `F` is known, so spawning an oversized or overaligned future is a build error,
+// not a task that silently never runs. `cargo check` skips them; `cargo build` reports them.
+#[cfg_attr(feature = "heap-executor", allow(dead_code))]
+const fn task_storage_needed<F, const S: usize>() -> usize {
+    const { assert!(core::mem::size_of::<F>() <= S, "future too big for a task slot") };
+    const { assert!(core::mem::align_of::<F>() <= TASK_STORAGE_ALIGN, "future too strictly aligned for a task slot") };
+    core::mem::size_of::<F>()
+}
+
+#[cfg_attr(feature = "heap-executor", allow(dead_code))]
+impl<const S: usize> Task<S> {
     fn new() -> Self {
         Self {
             poll_fn: None,
             drop_fn: None,
-            storage: [0; 512],
+            storage: TaskStorage([0; S]),
+            size: 0,
+            type_name: "",
+            id: TaskId(0),
+            priority: Priority::Normal,
         }
     }
     
     // Initialize with a future by copying its state
     fn init_with<F: Future<Output = ()> + 'static>(&mut self, future: F) {
-        let size = core::mem::size_of::<F>();
-        if size <= self.storage.len() {
-            unsafe {
-                // Copy the future into our storage
-                core::ptr::copy_nonoverlapping(
-                    &future as *const F as *const u8,
-                    self.storage.as_mut_ptr(),
-                    size
-                );
-            }
-            
-            // Set up function pointers for this specific future type
-            self.poll_fn = Some(|storage: *mut u8, cx: &mut Context<'_>| {
-                let future_ptr = storage as *mut F;
-                let future_ref = unsafe { &mut *future_ptr };
-                unsafe { Pin::new_unchecked(future_ref).poll(cx) }
-            });
-            
-            self.drop_fn = Some(|storage: *mut u8| {
-                let future_ptr = storage as *mut F;
-                unsafe { core::ptr::drop_in_place(future_ptr); }
-            });
-            
-            core::mem::forget(future); // Don't drop the original
+        let size = task_storage_needed::<F, S>(); // can't be more than the storage holds
+        self.size = size;
+        self.type_name = core::any::type_name::<F>();
+        
+        unsafe {
+            // Copy the future into our storage
+            core::ptr::copy_nonoverlapping(
+                &future as *const F as *const u8,
+                self.storage.as_mut_ptr(),
+                size
+            );
         }
+        
+        // Set up function pointers for this specific future type
+        self.poll_fn = Some(|storage: *mut u8, cx: &mut Context<'_>| {
+            let future_ptr = storage as *mut F;
+            let future_ref = unsafe { &mut *future_ptr };
+            unsafe { Pin::new_unchecked(future_ref).poll(cx) }
+        });
+        
+        self.drop_fn = Some(|storage: *mut u8| {
+            let future_ptr = storage as *mut F;
+            unsafe { core::ptr::drop_in_place(future_ptr); }
+        });
+        
+        core::mem::forget(future); // Don't drop the original
     }
     
     fn poll(&mut self, cx: &mut Context<'_>) -> Poll<()> {
@@ -83,72 +183,366 @@ impl Task {
     }
 }
 
-// Simple executor that runs tasks cooperatively
-struct Executor {
-    tasks: [Task; 8], // Max 8 concurrent tasks - using static allocation
-    current_task: usize,
+// Handed out by `spawn`. Ids count up and never repeat, so a finished task's id
+// doesn't come back to life when its slot is reused.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct TaskId(usize);
+
+// Low-priority tasks are for background housekeeping: they only get polled when no
+// normal task is ready, or after being passed over LOW_PRIORITY_PATIENCE times in a row
+// so they can't starve completely
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Priority {
+    Low,
+    Normal,
+}
+
+#[cfg_attr(feature = "heap-executor", allow(dead_code))]
+const LOW_PRIORITY_PATIENCE: u32 = 8;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum SpawnError {
+    NoFreeSlot, // every slot holds a running task
 }
 
-impl Executor {
+// Simple executor that runs tasks cooperatively: up to `N` at once (MAX_SLOTS at most),
+// each in `S` bytes of storage
+#[cfg_attr(feature = "heap-executor", allow(dead_code))]
+struct Executor<const N: usize, const S: usize = TASK_STORAGE> {
+    tasks: [Task<S>; N], // using static allocation
+    current_task: usize, // where the round-robin search for the next task starts
+    next_id: usize,
+    low_passed_over: u32, // steps in a row a ready low-priority task didn't get
+}
+
+#[cfg_attr(feature = "heap-executor", allow(dead_code))]
+impl<const N: usize, const S: usize> Executor<N, S> {
     fn new() -> Self {
+        const { assert!(N <= MAX_SLOTS, "more task slots than the per-slot flags have bits") };
         Self {
-            tasks: [
-                Task::new(), Task::new(), Task::new(), Task::new(),
-                Task::new(), Task::new(), Task::new(), Task::new()
-            ],
+            tasks: core::array::from_fn(|_| Task::new()),
             current_task: 0,
+            next_id: 0,
+            low_passed_over: 0,
         }
     }
 
-    fn spawn<F: Future<Output = ()> + 'static>(&mut self, future: F) -> bool {
-        for task in &mut self.tasks {
+    fn spawn<F: Future<Output = ()> + 'static>(&mut self, future: F) -> Result<TaskId, SpawnError> {
+        self.spawn_task(future, false, Priority::Normal)
+    }
+
+    // Pinned and low priority: keeps going while paused, but never takes a turn from an app
+    // that's ready to run
+    fn spawn_background<F: Future<Output = ()> + 'static>(&mut self, future: F) -> Result<TaskId, SpawnError> {
+        self.spawn_task(future, true, Priority::Low)
+    }
+
+    fn spawn_task<F: Future<Output = ()> + 'static>(&mut self, future: F, pinned: bool, priority: Priority) -> Result<TaskId, SpawnError> {
+        for (slot, task) in self.tasks.iter_mut().enumerate() {
             if !task.is_active() {
+                STOP_REQUESTS.fetch_and(!(1 << slot), Ordering::Relaxed);
                 task.init_with(future);
-                return true;
+                task.id = TaskId(self.next_id);
+                self.next_id += 1;
+                task.priority = priority;
+                set_task_pinned(slot, pinned);
+                TASK_READY.fetch_or(1 << slot, Ordering::Relaxed); // first poll happens unprompted
+                event_log::record(Event::TaskSpawned(slot));
+                return Ok(task.id);
+            }
+        }
+        event_log::record(Event::SpawnFailed);
+        Err(SpawnError::NoFreeSlot)
+    }
+
+    fn is_finished(&self, id: TaskId) -> bool {
+        !self.tasks.iter().any(|task| task.is_active() && task.id == id)
+    }
+
+    // Stop `id` without asking: its future is dropped where it stands, so its destructors
+    // still run (a `speaker::BeepGuard` it holds silences the speaker). Does nothing if
+    // the task already finished.
+    fn cancel(&mut self, id: TaskId) {
+        let Some(slot) = self.tasks.iter().position(|task| task.is_active() && task.id == id) else {
+            return;
+        };
+        self.tasks[slot].deactivate();
+        TASK_READY.fetch_and(!(1 << slot), Ordering::Relaxed);
+        event_log::record(Event::TaskCancelled(slot));
+    }
+
+    // Slot that gets the next poll: the first ready one at or after `current_task`, out of
+    // the normal-priority tasks unless no normal task is ready or the low ones have waited
+    // long enough
+    fn pick_task(&mut self) -> Option<usize> {
+        let mut active = 0u32;
+        let mut low = 0u32;
+        for (slot, task) in self.tasks.iter().enumerate() {
+            if task.is_active() {
+                active |= 1 << slot;
+                if task.priority == Priority::Low {
+                    low |= 1 << slot;
+                }
             }
         }
-        false // No free slots
+        TASK_READY.fetch_and(active, Ordering::Relaxed); // stale wakeups for finished tasks
+        
+        let ready = runnable_tasks() & active;
+        let candidates = if ready & low != 0 && (ready & !low == 0 || self.low_passed_over >= LOW_PRIORITY_PATIENCE) {
+            self.low_passed_over = 0;
+            ready & low
+        } else {
+            if ready & low != 0 {
+                self.low_passed_over += 1;
+            }
+            ready & !low
+        };
+        (0..self.tasks.len())
+            .map(|i| (self.current_task + i) % self.tasks.len())
+            .find(|&slot| candidates & (1 << slot) != 0)
     }
 
+    // Poll one task, the cooperative unit of work
     fn run_step(&mut self) {
-        // Round-robin through tasks
-        for _ in 0..self.tasks.len() {
-            let task = &mut self.tasks[self.current_task];
+        let Some(slot) = self.pick_task() else {
+            return;
+        };
+        // Cleared before polling, so a wake during the poll asks for another one
+        TASK_READY.fetch_and(!(1 << slot), Ordering::Relaxed);
+        CURRENT_SLOT.store(slot, Ordering::Relaxed);
+        let waker = executor_waker(slot);
+        let mut context = Context::from_waker(&waker);
+        
+        let task = &mut self.tasks[slot];
+        if let Poll::Ready(()) = task.poll(&mut context) {
+            // Task completed, deactivate it
+            task.deactivate();
+            event_log::record(Event::TaskFinished(slot));
+        }
+        self.current_task = (slot + 1) % self.tasks.len();
+    }
+
+    // Ask `id` to wind down. Purely cooperative: the task only notices through
+    // `should_stop()`, and keeps running until it returns on its own. It gets woken, so it
+    // doesn't first have to wait out whatever it was sleeping on. Does nothing if the task
+    // already finished.
+    fn request_stop(&self, id: TaskId) {
+        let Some(slot) = self.tasks.iter().position(|task| task.is_active() && task.id == id) else {
+            return;
+        };
+        STOP_REQUESTS.fetch_or(1 << slot, Ordering::Relaxed);
+        TASK_READY.fetch_or(1 << slot, Ordering::Relaxed);
+    }
+
+    // Freeze every task that wasn't spawned with `spawn_background`, or let them go again.
+    // Safe to call from interrupt handlers, which is the only place a frozen app's keys
+    // still get noticed.
+    fn set_paused(paused: bool) {
+        PAUSED.store(paused, Ordering::Relaxed);
+        if !paused {
+            Self::wake_all();
+        }
+    }
+
+    fn toggle_paused() {
+        if PAUSED.fetch_xor(true, Ordering::Relaxed) {
+            Self::wake_all(); // was paused, now isn't
+        }
+    }
+
+    // ESC: asks the task `run_until` is waiting on to stop, leaving the background tasks
+    // alone. Safe to call from interrupt handlers.
+    fn stop_key() {
+        if FOREGROUND_WAIT.load(Ordering::Relaxed) {
+            Self::set_paused(false); // a frozen app couldn't see the stop request
+            STOP_FOREGROUND.store(true, Ordering::Relaxed);
+        }
+    }
+
+    // F12: cancels the task `run_until` is waiting on, for an app that stopped listening
+    // to ESC. Safe to call from interrupt handlers.
+    fn panic_key() {
+        if FOREGROUND_WAIT.load(Ordering::Relaxed) {
+            Self::set_paused(false); // whatever takes over has to run
+            CANCEL_FOREGROUND.store(true, Ordering::Relaxed);
+        }
+    }
+
+    // Every task gets polled on the next pass, whatever it was waiting for. Tasks just
+    // check their condition again, so this is always safe, only not free.
+    fn wake_all() {
+        TASK_READY.store(u32::MAX, Ordering::Relaxed);
+    }
+
+    // Log how much of its storage each active slot uses
+    fn dump_slot_sizes(&self) {
+        for (slot, task) in self.tasks.iter().enumerate() {
             if task.is_active() {
-                let waker = dummy_waker();
-                let mut context = Context::from_waker(&waker);
-                
-                match task.poll(&mut context) {
-                    Poll::Ready(()) => {
-                        // Task completed, deactivate it
-                        task.deactivate();
-                    }
-                    Poll::Pending => {
-                        // Task is still running, continue
-                    }
+                log_info!("slot {}: {}/{} bytes - {}", slot, task.size, S, task.type_name);
+            }
+        }
+    }
+
+    fn run_pass(&mut self) {
+        for _ in 0..self.tasks.len() {
+            self.run_step();
+        }
+    }
+
+    // Own the main loop until `id` finishes, running every task, not just `id`, one full
+    // round-robin pass at a time. Once no task is left ready, the CPU halts until an
+    // interrupt (timer tick or key press) wakes one. Every pass pets the watchdog, so a
+    // task stuck inside `poll` ends on the panic screen. Meanwhile ESC asks `id` to stop,
+    // and F12 cancels it, for an app that stopped listening to ESC.
+    fn run_until(&mut self, id: TaskId) {
+        start_foreground_wait();
+        loop {
+            watchdog::watchdog_pet();
+            if foreground_stop_requested() {
+                self.request_stop(id);
+            }
+            if foreground_cancel_requested() {
+                self.cancel(id);
+            }
+            self.run_pass();
+            if self.is_finished(id) {
+                break;
+            }
+            halt_until_ready();
+        }
+        FOREGROUND_WAIT.store(false, Ordering::Relaxed);
+    }
+    
+    // Own the main loop for good. The menu, along with whatever app it's running, is the
+    // foreground task `run_until` waits on; it only ever ends when F12 cancels it, and then
+    // a fresh one takes over.
+    fn run(&mut self) -> ! {
+        loop {
+            match self.spawn(menu(*MEMTEST_REGION.lock())) {
+                Ok(menu) => {
+                    self.dump_slot_sizes();
+                    self.run_until(menu);
+                    foreground_cancelled();
                 }
+                Err(error) => warn_spawn_failed("menu", error),
             }
-            
-            self.current_task = (self.current_task + 1) % self.tasks.len();
-            break; // Only run one task per step for cooperative scheduling
         }
     }
 }
 
-// Dummy waker for our simple executor
-fn dummy_waker() -> Waker {
+// Halt the CPU unless some task is already ready to run
+fn halt_until_ready() {
+    // Check and sleep with interrupts off so a wakeup can't slip in between
+    interrupts::disable();
+    if runnable_tasks() != 0 {
+        interrupts::enable();
+    } else {
+        let before = timer::ticks();
+        interrupts::enable_and_halt();
+        HALTED_TICKS.fetch_add(timer::ticks() - before, Ordering::Relaxed);
+    }
+}
+
+// The executor `_start` runs: heap-backed with the `heap-executor` feature, fixed slots otherwise
+#[cfg(feature = "heap-executor")]
+type KernelExecutor = heap_executor::HeapExecutor;
+#[cfg(not(feature = "heap-executor"))]
+type KernelExecutor = DefaultExecutor;
+
+// Eight slots of TASK_STORAGE bytes each
+type DefaultExecutor = Executor<8>;
+
+// Timer ticks that went by with the CPU halted in `run_until`
+static HALTED_TICKS: AtomicU64 = AtomicU64::new(0);
+
+// See `Executor::set_paused`
+static PAUSED: AtomicBool = AtomicBool::new(false);
+
+// Set while `run_until` waits on a task, the only time F12 has something to cancel
+static FOREGROUND_WAIT: AtomicBool = AtomicBool::new(false);
+// ESC went down during `run_until`, see `Executor::stop_key`
+static STOP_FOREGROUND: AtomicBool = AtomicBool::new(false);
+// F12 went up during `run_until`, see `Executor::panic_key`
+static CANCEL_FOREGROUND: AtomicBool = AtomicBool::new(false);
+
+// An ESC or F12 from before the wait doesn't count
+fn start_foreground_wait() {
+    STOP_FOREGROUND.store(false, Ordering::Relaxed);
+    CANCEL_FOREGROUND.store(false, Ordering::Relaxed);
+    FOREGROUND_WAIT.store(true, Ordering::Relaxed);
+}
+
+fn foreground_stop_requested() -> bool {
+    STOP_FOREGROUND.swap(false, Ordering::Relaxed)
+}
+
+fn foreground_cancel_requested() -> bool {
+    CANCEL_FOREGROUND.swap(false, Ordering::Relaxed)
+}
+
+// Clean up after an app F12 took down mid-run, before `run` brings up a fresh menu
+fn foreground_cancelled() {
+    log_warn!("F12: foreground task cancelled, back to the menu");
+    vga::disable_cursor(); // in case it was the shell
+    keyboard::flush();
+}
+
+// The per-slot flags below are bits of a u32, so no executor can have more slots
+const MAX_SLOTS: usize = 32;
+
+// One bit per slot, see `Executor::request_stop`
+static STOP_REQUESTS: AtomicU32 = AtomicU32::new(0);
+// Slot of the task currently being polled
+static CURRENT_SLOT: AtomicUsize = AtomicUsize::new(0);
+
+// Has the running task been asked to stop? This is how apps notice ESC: `run_until` turns
+// it into a stop request for the foreground task, so no app has to look for the key itself.
+fn should_stop() -> bool {
+    STOP_REQUESTS.load(Ordering::Relaxed) & (1 << CURRENT_SLOT.load(Ordering::Relaxed)) != 0
+}
+
+// Forget any stop request aimed at the running task
+fn clear_stop() {
+    STOP_REQUESTS.fetch_and(!(1 << CURRENT_SLOT.load(Ordering::Relaxed)), Ordering::Relaxed);
+}
+
+// One bit per slot, set by that task's waker; only ready tasks get polled. Atomic because
+// wakers fire from interrupt handlers (timer deadlines, keyboard input).
+static TASK_READY: AtomicU32 = AtomicU32::new(0);
+// One bit per slot spawned with `spawn_background`
+static PINNED_TASKS: AtomicU32 = AtomicU32::new(0);
+
+fn set_task_pinned(slot: usize, pinned: bool) {
+    if pinned {
+        PINNED_TASKS.fetch_or(1 << slot, Ordering::Relaxed);
+    } else {
+        PINNED_TASKS.fetch_and(!(1 << slot), Ordering::Relaxed);
+    }
+}
+
+// Slots that are ready and allowed to run. Paused, only pinned tasks are; the rest
+// stay frozen mid-frame, keeping their ready bit for when the pause ends.
+fn runnable_tasks() -> u32 {
+    let ready = TASK_READY.load(Ordering::Relaxed);
+    if PAUSED.load(Ordering::Relaxed) {
+        ready & PINNED_TASKS.load(Ordering::Relaxed)
+    } else {
+        ready
+    }
+}
+
+// Waker for the task in `slot`: the slot number rides along as the data pointer, and
+// waking just sets the task's ready bit
+fn executor_waker(slot: usize) -> Waker {
     use core::task::{RawWaker, RawWakerVTable};
     
-    fn clone(_: *const ()) -> RawWaker { dummy_raw_waker() }
-    fn wake(_: *const ()) {}
-    fn wake_by_ref(_: *const ()) {}
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake, drop);
+    
+    fn clone(data: *const ()) -> RawWaker { RawWaker::new(data, &VTABLE) }
+    fn wake(data: *const ()) { TASK_READY.fetch_or(1 << (data as usize), Ordering::Relaxed); }
     fn drop(_: *const ()) {}
 
-    fn dummy_raw_waker() -> RawWaker {
-        RawWaker::new(core::ptr::null(), &RawWakerVTable::new(clone, wake, wake_by_ref, drop))
-    }
-
-    unsafe { Waker::from_raw(dummy_raw_waker()) }
+    unsafe { Waker::from_raw(RawWaker::new(slot as *const (), &VTABLE)) }
 }
 
 // === ASYNC UTILITIES ===
@@ -167,11 +561,12 @@ impl Yield {
 impl Future for Yield {
     type Output = ();
 
-    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         if self.yielded {
             Poll::Ready(())
         } else {
             self.yielded = true;
+            cx.waker().wake_by_ref(); // ready again right away, just not before the others
             Poll::Pending
         }
     }
@@ -181,510 +576,3026 @@ async fn yield_now() {
     Yield::new().await;
 }
 
-// Async delay
-struct Delay {
+// Yields `count` times in a row. Unlike the old `Delay`, or `timer::sleep_ms` that replaced
+// it, which wait out wall-clock time, this only counts scheduling hops: how long it takes
+// depends on what the other tasks do.
+struct YieldN {
     remaining: u32,
 }
 
-impl Delay {
-    fn new(cycles: u32) -> Self {
-        Self { remaining: cycles }
-    }
-}
-
-impl Future for Delay {
+impl Future for YieldN {
     type Output = ();
 
-    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         if self.remaining == 0 {
             Poll::Ready(())
         } else {
-            // Decrement in chunks to avoid blocking too long
-            let chunk = self.remaining.min(1000);
-            self.remaining -= chunk;
-            for _ in 0..chunk {
-                unsafe { core::arch::asm!("nop"); }
-            }
+            self.remaining -= 1;
+            cx.waker().wake_by_ref();
             Poll::Pending
         }
     }
 }
 
-async fn delay(cycles: u32) {
-    Delay::new(cycles).await;
+fn yield_n(count: u32) -> YieldN {
+    YieldN { remaining: count }
 }
 
-// === VGA AND INPUT ===
+// One side of a `join`: still running, finished with its output parked, or emptied
+enum MaybeDone<F: Future> {
+    Running(F),
+    Done(F::Output),
+    Taken,
+}
 
-// Clear the screen
-fn clear_screen() {
-    let vga_buffer = 0xb8000 as *mut u8;
-    for i in 0..80*25 {
-        unsafe {
-            *vga_buffer.offset(i * 2) = b' ';
-            *vga_buffer.offset(i * 2 + 1) = 0x07;
+impl<F: Future> MaybeDone<F> {
+    // Drive the future if it's still running; true once an output is waiting
+    fn poll_in_place(self: Pin<&mut Self>, cx: &mut Context<'_>) -> bool {
+        // Safety: the future is only ever polled where it sits, and replaced only after it's done
+        let this = unsafe { self.get_unchecked_mut() };
+        match this {
+            MaybeDone::Running(future) => {
+                if let Poll::Ready(output) = unsafe { Pin::new_unchecked(future) }.poll(cx) {
+                    *this = MaybeDone::Done(output);
+                    true
+                } else {
+                    false
+                }
+            }
+            MaybeDone::Done(_) => true,
+            MaybeDone::Taken => false,
         }
     }
-}
 
-// Write text at specific position
-fn write_at(text: &[u8], row: usize, col: usize, color: u8) {
-    let vga_buffer = 0xb8000 as *mut u8;
-    let offset = (row * 80 + col) * 2;
-    
-    for (i, &byte) in text.iter().enumerate() {
-        if offset + i * 2 < 80 * 25 * 2 {
-            unsafe {
-                *vga_buffer.offset((offset + i * 2) as isize) = byte;
-                *vga_buffer.offset((offset + i * 2 + 1) as isize) = color;
-            }
+    fn take_output(self: Pin<&mut Self>) -> F::Output {
+        let this = unsafe { self.get_unchecked_mut() };
+        match core::mem::replace(this, MaybeDone::Taken) {
+            MaybeDone::Done(output) => output,
+            _ => unreachable!("join output taken twice"),
         }
     }
 }
 
-// Write single character at position
-fn write_char_at(ch: u8, row: usize, col: usize, color: u8) {
-    if row < 25 && col < 80 {
-        let vga_buffer = 0xb8000 as *mut u8;
-        let offset = (row * 80 + col) * 2;
-        unsafe {
-            *vga_buffer.offset(offset as isize) = ch;
-            *vga_buffer.offset((offset + 1) as isize) = color;
+// Runs two futures inside one task until both finish. Every poll drives each unfinished
+// future, so `b` still gets polled in the same call where `a` completes.
+struct Join<A: Future, B: Future> {
+    a: MaybeDone<A>,
+    b: MaybeDone<B>,
+}
+
+impl<A: Future, B: Future> Future for Join<A, B> {
+    type Output = (A::Output, B::Output);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = unsafe { self.get_unchecked_mut() };
+        let (mut a, mut b) = unsafe { (Pin::new_unchecked(&mut this.a), Pin::new_unchecked(&mut this.b)) };
+        let a_done = a.as_mut().poll_in_place(cx);
+        let b_done = b.as_mut().poll_in_place(cx);
+        if a_done && b_done {
+            Poll::Ready((a.take_output(), b.take_output()))
+        } else {
+            Poll::Pending
         }
     }
 }
 
-// Read from keyboard port
-fn read_keyboard() -> Option<u8> {
-    unsafe {
-        let status: u8;
-        core::arch::asm!("in al, 0x64", out("al") status);
-        
-        if status & 0x01 != 0 {
-            let scan_code: u8;
-            core::arch::asm!("in al, 0x60", out("al") scan_code);
-            Some(scan_code)
+fn join<A: Future, B: Future>(a: A, b: B) -> Join<A, B> {
+    Join { a: MaybeDone::Running(a), b: MaybeDone::Running(b) }
+}
+
+struct Join3<A: Future, B: Future, C: Future> {
+    a: MaybeDone<A>,
+    b: MaybeDone<B>,
+    c: MaybeDone<C>,
+}
+
+impl<A: Future, B: Future, C: Future> Future for Join3<A, B, C> {
+    type Output = (A::Output, B::Output, C::Output);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = unsafe { self.get_unchecked_mut() };
+        let (mut a, mut b, mut c) = unsafe {
+            (Pin::new_unchecked(&mut this.a), Pin::new_unchecked(&mut this.b), Pin::new_unchecked(&mut this.c))
+        };
+        let a_done = a.as_mut().poll_in_place(cx);
+        let b_done = b.as_mut().poll_in_place(cx);
+        let c_done = c.as_mut().poll_in_place(cx);
+        if a_done && b_done && c_done {
+            Poll::Ready((a.take_output(), b.take_output(), c.take_output()))
         } else {
-            None
+            Poll::Pending
         }
     }
 }
 
-// === RANDOM NUMBER GENERATOR ===
+fn join3<A: Future, B: Future, C: Future>(a: A, b: B, c: C) -> Join3<A, B, C> {
+    Join3 { a: MaybeDone::Running(a), b: MaybeDone::Running(b), c: MaybeDone::Running(c) }
+}
 
-static mut RNG_STATE: u32 = 12345;
+// Which future of a `select2` finished first, with its output
+#[derive(Debug, PartialEq, Eq)]
+enum Either<A, B> {
+    First(A),
+    Second(B),
+}
 
-fn random() -> u32 {
-    unsafe {
-        RNG_STATE = RNG_STATE.wrapping_mul(1103515245).wrapping_add(12345);
-        RNG_STATE
+// Runs two futures inside one task until either one finishes. The other is dropped on
+// the spot, before the output is handed back, so anything it was holding gets let go.
+// `a` is polled first and wins if both are ready in the same poll.
+struct Select2<A: Future, B: Future> {
+    a: Option<A>,
+    b: Option<B>,
+}
+
+impl<A: Future, B: Future> Future for Select2<A, B> {
+    type Output = Either<A::Output, B::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: both futures are polled where they sit and dropped in place, never moved
+        let this = unsafe { self.get_unchecked_mut() };
+        if let Some(a) = &mut this.a
+            && let Poll::Ready(output) = unsafe { Pin::new_unchecked(a) }.poll(cx)
+        {
+            this.a = None;
+            this.b = None;
+            return Poll::Ready(Either::First(output));
+        }
+        if let Some(b) = &mut this.b
+            && let Poll::Ready(output) = unsafe { Pin::new_unchecked(b) }.poll(cx)
+        {
+            this.a = None;
+            this.b = None;
+            return Poll::Ready(Either::Second(output));
+        }
+        Poll::Pending
     }
 }
 
-fn get_random_char() -> u8 {
-    let chars = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789!@#$%^&*()SWAG";
-    chars[(random() % chars.len() as u32) as usize]
+fn select2<A: Future, B: Future>(a: A, b: B) -> Select2<A, B> {
+    Select2 { a: Some(a), b: Some(b) }
 }
 
-fn get_random_color() -> u8 {
-    let colors = [0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x02, 0x03, 0x05, 0x06];
-    colors[(random() % colors.len() as u32) as usize]
+// === COLORS ===
+
+// The 16 text mode colors. Backgrounds only get the first 8 unless blinking is off.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u8)]
+enum Color {
+    Black = 0,
+    Blue = 1,
+    Green = 2,
+    Cyan = 3,
+    Red = 4,
+    Magenta = 5,
+    Brown = 6,
+    LightGray = 7,
+    DarkGray = 8,
+    LightBlue = 9,
+    LightGreen = 10,
+    LightCyan = 11,
+    LightRed = 12,
+    LightMagenta = 13,
+    Yellow = 14,
+    White = 15,
 }
 
-// === PANIC HANDLER ===
+// VGA attribute byte: foreground in the low nibble, background in bits 4-6, blink in bit 7
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct ColorCode(u8);
 
-#[panic_handler]
-fn panic(_info: &PanicInfo) -> ! {
-    clear_screen();
-    
-    let panic_messages = [
-        b"OH NO! MAXIMUM SWAG OVERLOAD!!!",
-        b"SYSTEM TOO SWAG TO HANDLE!!!!!!",
-        b"SWAG LEVELS: OVER 9000!!!!!!!!!",
-        b"ERROR: NOT ENOUGH SWAG DETECTED",
-        b"PANIC: SWAG BUFFER OVERFLOW!!!!",
-        b"CRITICAL: SWAG CORE MELTDOWN!!!"
-    ];
-    
-    let colors = [0x0c, 0x0e, 0x0a, 0x0b, 0x0d, 0x09];
-    let mut color_index = 0;
-    let mut message_index = 0;
-    
-    for _ in 0..20 {
-        clear_screen();
-        
-        let msg = panic_messages[message_index % panic_messages.len()];
-        let color = colors[color_index % colors.len()];
-        write_at(msg, 2, 24, color);
-        
-        write_at(b"KERNEL PANIC at swag_generator():line_MAX", 10, 18, 0x0f);
-        write_at(b"Stack trace: SWAG -> MORE_SWAG -> MAXIMUM_SWAG", 12, 16, 0x07);
-        write_at(b"Error code: 0xSWAG (cooperative multitasking overload)", 14, 12, 0x0c);
-        
-        write_at(b" $$$$$$\\  $$\\      $$\\  $$$$$$\\   $$$$$$\\", 16, 20, colors[color_index % colors.len()]);
-        write_at(b"$$  __$$\\ $$ | $\\  $$ |$$  __$$\\ $$  __$$\\", 17, 19, colors[(color_index + 1) % colors.len()]);
-        write_at(b"\\$$$$$$\\  $$ $$ $$\\$$ |$$$$$$$$ |$$ |$$$$\\", 18, 19, colors[(color_index + 2) % colors.len()]);
-        write_at(b" \\______/ \\__/     \\__|\\__|  \\__| \\______/", 19, 19, colors[(color_index + 3) % colors.len()]);
-        
-        write_at(b"System halted with MAXIMUM SWAG!", 22, 24, 0x08);
-        
-        color_index += 1;
-        message_index += 1;
-        
-        for _ in 0..50_000_000 {
-            unsafe { core::arch::asm!("nop"); }
-        }
+impl ColorCode {
+    const fn new(fg: Color, bg: Color) -> Self {
+        Self((bg as u8) << 4 | fg as u8)
+    }
+
+    // Same colors with bit 7 set: blinking text while `vga::enable_blink(true)`,
+    // a bright background otherwise
+    const fn blinking(self) -> Self {
+        Self(self.0 | 0x80)
+    }
+
+    // Foreground and background swapped. Bit 3 of the foreground doesn't come along, since
+    // as bit 7 it would make the text blink.
+    const fn inverted(self) -> Self {
+        Self((self.0 & 0x07) << 4 | (self.0 >> 4) & 0x07)
     }
-    
-    clear_screen();
-    write_at(b"SYSTEM SWAG OVERLOAD COMPLETE", 12, 25, 0x0c);
-    write_at(b"RIP SwagOS - Too Swag 4 This World", 14, 22, 0x08);
-    
-    loop {}
 }
 
-// === ASYNC APPLICATIONS ===
+// Everything the apps draw with, named after the foreground; the background is black
+// unless the name says otherwise
+const BLACK: ColorCode = ColorCode::new(Color::Black, Color::Black);
+const GREEN: ColorCode = ColorCode::new(Color::Green, Color::Black);
+const CYAN: ColorCode = ColorCode::new(Color::Cyan, Color::Black);
+const RED: ColorCode = ColorCode::new(Color::Red, Color::Black);
+const MAGENTA: ColorCode = ColorCode::new(Color::Magenta, Color::Black);
+const BROWN: ColorCode = ColorCode::new(Color::Brown, Color::Black);
+const LIGHT_GRAY: ColorCode = ColorCode::new(Color::LightGray, Color::Black);
+const DARK_GRAY: ColorCode = ColorCode::new(Color::DarkGray, Color::Black);
+const LIGHT_BLUE: ColorCode = ColorCode::new(Color::LightBlue, Color::Black);
+const LIGHT_GREEN: ColorCode = ColorCode::new(Color::LightGreen, Color::Black);
+const LIGHT_CYAN: ColorCode = ColorCode::new(Color::LightCyan, Color::Black);
+const LIGHT_RED: ColorCode = ColorCode::new(Color::LightRed, Color::Black);
+const LIGHT_MAGENTA: ColorCode = ColorCode::new(Color::LightMagenta, Color::Black);
+const YELLOW: ColorCode = ColorCode::new(Color::Yellow, Color::Black);
+const WHITE: ColorCode = ColorCode::new(Color::White, Color::Black);
+const WHITE_ON_RED: ColorCode = ColorCode::new(Color::White, Color::Red);
+const WHITE_ON_BLUE: ColorCode = ColorCode::new(Color::White, Color::Blue);
+const BLACK_ON_LIGHT_GRAY: ColorCode = ColorCode::new(Color::Black, Color::LightGray);
 
-async fn swag_generator() {
-    let colors = [0x0c, 0x0a, 0x0e, 0x0b, 0x0d, 0x09];
-    let mut current_line = 0;
-    let mut color_index = 0;
-    
-    loop {
-        // Check for ESC key
-        if let Some(scan_code) = read_keyboard() {
-            if scan_code == KEY_ESC {
-                break;
-            }
+// A named sequence of colors for cycling effects
+#[derive(Clone, Copy)]
+struct Palette {
+    colors: &'static [ColorCode],
+}
+
+impl Palette {
+    const fn new(colors: &'static [ColorCode]) -> Self {
+        Self { colors }
+    }
+
+    // Wraps around, for effects that loop through the colors forever
+    fn cycle(&self, step: usize) -> ColorCode {
+        self.colors[step % self.colors.len()]
+    }
+
+    // Sticks on the last color, for trails that fade out
+    fn fade(&self, step: usize) -> ColorCode {
+        self.colors[step.min(self.colors.len() - 1)]
+    }
+}
+
+const RAINBOW: Palette = Palette::new(&[
+    LIGHT_RED,
+    YELLOW,
+    LIGHT_GREEN,
+    LIGHT_CYAN,
+    LIGHT_BLUE,
+    LIGHT_MAGENTA,
+]);
+
+const FIRE: Palette = Palette::new(&[
+    RED,
+    LIGHT_RED,
+    BROWN,
+    YELLOW,
+    WHITE,
+    YELLOW,
+    LIGHT_RED,
+]);
+
+// Bright head fading into a dark green trail
+const MATRIX_GREEN: Palette = Palette::new(&[
+    WHITE,
+    LIGHT_GREEN,
+    LIGHT_GREEN,
+    GREEN,
+]);
+
+// === VGA AND INPUT ===
+
+const MAX_SCREEN_CELLS: usize = 80 * vga::MAX_ROWS;
+const BLANK_CELL: u16 = 0x0720; // space, light grey on black
+
+// One text cell: character in the low byte, color attribute in the high byte
+fn vga_cell(ch: u8, color: ColorCode) -> u16 {
+    (color.0 as u16) << 8 | ch as u16
+}
+
+// A text buffer and its size. `Vga::screen()` is the real one in whatever mode is set;
+// tests point one at an ordinary array so they can check what got drawn. Every access
+// goes through `Volatile` so the compiler can't drop or reorder writes it thinks nobody
+// reads.
+#[derive(Clone, Copy)]
+struct Vga {
+    buffer: *mut Volatile<u16>,
+    width: usize,
+    height: usize,
+}
+
+impl Vga {
+    // The standard 80x25 text screen at 0xb8000
+    const fn vga_text() -> Self {
+        Self { buffer: 0xb8000 as *mut Volatile<u16>, width: 80, height: 25 }
+    }
+
+    // The text screen as the current mode has it
+    fn screen() -> Self {
+        Self { width: screen_cols(), height: screen_rows(), ..Self::vga_text() }
+    }
+
+    // `buffer` has to stay valid for `width * height` cells while this is in use
+    #[cfg(test)]
+    const unsafe fn new(buffer: *mut Volatile<u16>, width: usize, height: usize) -> Self {
+        Self { buffer, width, height }
+    }
+
+    fn cells(&mut self) -> &mut [Volatile<u16>] {
+        unsafe { core::slice::from_raw_parts_mut(self.buffer, self.width * self.height) }
+    }
+
+    fn clear(&mut self) {
+        for cell in self.cells() {
+            cell.write(BLANK_CELL);
         }
-        
-        // Write SWAG at current line
-        let color = colors[color_index % colors.len()];
-        write_at(b"SWAG", current_line, 38, color);
-        
-        // Move to next line and wrap around
-        current_line = (current_line + 1) % 25;
-        color_index += 1;
-        
-        // If we've wrapped around, clear the screen
-        if current_line == 0 {
-            delay(5_000_000).await;
-            clear_screen();
+    }
+
+    // Write text, stopping at the end of the row instead of spilling into the next one.
+    // Returns how many characters actually made it onto the screen.
+    fn write_at_clipped(&mut self, text: &[u8], row: usize, col: usize, color: ColorCode) -> usize {
+        if row >= self.height || col >= self.width {
+            return 0;
         }
-        
-        delay(500_000).await;
-        yield_now().await;
+        let visible = text.len().min(self.width - col);
+        let start = row * self.width + col;
+        for (cell, &byte) in self.cells()[start..][..visible].iter_mut().zip(text) {
+            cell.write(vga_cell(byte, color));
+        }
+        visible
     }
-}
 
-async fn swag_matrix() {
-    let mut columns: [u8; 80] = [0; 80];
-    let mut column_speeds: [u8; 80] = [1; 80];
-    
-    // Initialize random speeds and positions
-    for i in 0..80 {
-        column_speeds[i] = ((random() % 3) + 1) as u8;
-        columns[i] = (random() % 25) as u8;
+    fn write_char_at(&mut self, ch: u8, row: usize, col: usize, color: ColorCode) {
+        self.update_cell(row, col, |_| vga_cell(ch, color));
     }
-    
-    loop {
-        // Check for ESC key
-        if let Some(scan_code) = read_keyboard() {
-            if scan_code == KEY_ESC {
-                break;
-            }
+
+    fn read_char_at(&mut self, row: usize, col: usize) -> (u8, ColorCode) {
+        if row < self.height && col < self.width {
+            let index = row * self.width + col;
+            let cell = self.cells()[index].read();
+            (cell as u8, ColorCode((cell >> 8) as u8))
+        } else {
+            (b' ', LIGHT_GRAY)
         }
-        
-        // Update each column
-        for col in 0..80 {
-            columns[col] = (columns[col] + column_speeds[col]) % 25;
-            
-            // Clear the old trail
-            for trail in 0..5 {
-                let clear_row = if columns[col] >= trail { 
-                    columns[col] - trail 
-                } else { 
-                    25 + columns[col] - trail 
-                };
-                if clear_row < 25 {
-                    write_at(b" ", clear_row as usize, col, 0x00);
-                }
+    }
+
+    // Volatile read-modify-write of one cell; off-screen positions are ignored
+    fn update_cell(&mut self, row: usize, col: usize, f: impl FnOnce(u16) -> u16) {
+        if row < self.height && col < self.width {
+            let width = self.width;
+            let cell = &mut self.cells()[row * width + col];
+            cell.write(f(cell.read()));
+        }
+    }
+
+    // Fill a rectangle with one character, clipped to the buffer
+    fn fill_region(&mut self, row: usize, col: usize, width: usize, height: usize, ch: u8, color: ColorCode) {
+        for r in row..(row + height).min(self.height) {
+            for c in col..(col + width).min(self.width) {
+                self.write_char_at(ch, r, c, color);
+            }
+        }
+    }
+}
+
+// Clear the screen
+fn clear_screen() {
+    Vga::screen().clear();
+}
+
+// Write text at specific position
+fn write_at(text: &[u8], row: usize, col: usize, color: ColorCode) {
+    write_at_clipped(text, row, col, color);
+}
+
+// Write text, stopping at the end of the row instead of spilling into the next one.
+// Returns how many characters actually made it onto the screen.
+fn write_at_clipped(text: &[u8], row: usize, col: usize, color: ColorCode) -> usize {
+    Vga::screen().write_at_clipped(text, row, col, color)
+}
+
+// Write single character at position
+fn write_char_at(ch: u8, row: usize, col: usize, color: ColorCode) {
+    Vga::screen().write_char_at(ch, row, col, color);
+}
+
+// Write text centered on a row; text wider than the screen is cut off on the right
+fn write_centered(text: &[u8], row: usize, color: ColorCode) {
+    write_at_clipped(text, row, 80usize.saturating_sub(text.len()) / 2, color);
+}
+
+// Character and color currently shown at a position
+fn read_char_at(row: usize, col: usize) -> (u8, ColorCode) {
+    Vga::screen().read_char_at(row, col)
+}
+
+// Change only the foreground (low) nibble of a cell's attribute
+fn set_fg(row: usize, col: usize, fg: u8) {
+    update_cell(row, col, |cell| (cell & 0xf0ff) | ((fg as u16 & 0x0f) << 8));
+}
+
+// Change only the background (high) nibble of a cell's attribute
+fn set_bg(row: usize, col: usize, bg: u8) {
+    update_cell(row, col, |cell| (cell & 0x0fff) | ((bg as u16 & 0x0f) << 12));
+}
+
+// Replace the character but leave whatever colors the cell already has
+fn set_char_keep_color(row: usize, col: usize, ch: u8) {
+    update_cell(row, col, |cell| (cell & 0xff00) | ch as u16);
+}
+
+// Volatile read-modify-write of one on-screen cell
+fn update_cell(row: usize, col: usize, f: impl FnOnce(u16) -> u16) {
+    Vga::screen().update_cell(row, col, f);
+}
+
+// Draw a character in the foreground color of `color`. A black background counts as
+// transparent: the cell keeps the background it already had.
+fn overlay_char_at(ch: u8, row: usize, col: usize, color: ColorCode) {
+    set_char_keep_color(row, col, ch);
+    set_fg(row, col, color.0);
+    if color.0 & 0xf0 != 0 {
+        set_bg(row, col, color.0 >> 4);
+    }
+}
+
+// Write a number in decimal, returns how many digits were written
+fn write_u32(value: u32, row: usize, col: usize, color: ColorCode) -> usize {
+    let mut digits = [0u8; 10];
+    let mut start = digits.len();
+    let mut remaining = value;
+    loop {
+        start -= 1;
+        digits[start] = b'0' + (remaining % 10) as u8;
+        remaining /= 10;
+        if remaining == 0 {
+            break;
+        }
+    }
+    write_at(&digits[start..], row, col, color);
+    digits.len() - start
+}
+
+// Fill a rectangle with one character, clipped to the screen
+fn fill_region(row: usize, col: usize, width: usize, height: usize, ch: u8, color: ColorCode) {
+    Vga::screen().fill_region(row, col, width, height, ch, color);
+}
+
+// CP437 double-line box pieces
+const BOX_TOP_LEFT: u8 = 0xc9;
+const BOX_TOP_RIGHT: u8 = 0xbb;
+const BOX_BOTTOM_LEFT: u8 = 0xc8;
+const BOX_BOTTOM_RIGHT: u8 = 0xbc;
+const BOX_HORIZONTAL: u8 = 0xcd;
+const BOX_VERTICAL: u8 = 0xba;
+
+// Frame a rectangle, the frame included in `height` and `width`, clipped to the screen.
+// Anything under 2x2 has no room for its corners, so it draws nothing. Fill the inside
+// with `fill_region`.
+fn draw_box(top: usize, left: usize, height: usize, width: usize, color: ColorCode) {
+    if height < 2 || width < 2 {
+        return;
+    }
+    let (bottom, right) = (top + height - 1, left + width - 1);
+    fill_region(top, left + 1, width - 2, 1, BOX_HORIZONTAL, color);
+    fill_region(bottom, left + 1, width - 2, 1, BOX_HORIZONTAL, color);
+    fill_region(top + 1, left, 1, height - 2, BOX_VERTICAL, color);
+    fill_region(top + 1, right, 1, height - 2, BOX_VERTICAL, color);
+    write_char_at(BOX_TOP_LEFT, top, left, color);
+    write_char_at(BOX_TOP_RIGHT, top, right, color);
+    write_char_at(BOX_BOTTOM_LEFT, bottom, left, color);
+    write_char_at(BOX_BOTTOM_RIGHT, bottom, right, color);
+}
+
+// Outline a circle of radius `r` with the midpoint algorithm, handing `plot` each cell
+// as an offset from the center: one cell per step around each octant, so there are no
+// gaps however big it gets. Cells are about twice as tall as they are wide, so on screen
+// it comes out as a tall ellipse.
+fn circle_outline(r: i32, mut plot: impl FnMut(i32, i32)) {
+    let (mut x, mut y, mut err) = (r, 0, 1 - r);
+    while x >= y {
+        for (dx, dy) in [(x, y), (y, x), (-y, x), (-x, y), (-x, -y), (-y, -x), (y, -x), (x, -y)] {
+            plot(dx, dy);
+        }
+        y += 1;
+        if err < 0 {
+            err += 2 * y + 1;
+        } else {
+            x -= 1;
+            err += 2 * (y - x) + 1;
+        }
+    }
+}
+
+// One screen row worth of formatted text; anything past column 80 is cut off
+struct ScreenLine {
+    buf: [u8; 80],
+    len: usize,
+}
+
+impl ScreenLine {
+    fn new() -> Self {
+        Self { buf: [0; 80], len: 0 }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+}
+
+impl fmt::Write for ScreenLine {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            if self.len == self.buf.len() {
+                break;
+            }
+            self.buf[self.len] = byte;
+            self.len += 1;
+        }
+        Ok(())
+    }
+}
+
+// Sprite cells with this character are transparent and leave the screen untouched
+const SPRITE_TRANSPARENT: u8 = 0x00;
+
+// Rectangular block of (char, color) cells, stored row by row
+struct Sprite {
+    width: usize,
+    height: usize,
+    cells: &'static [(u8, ColorCode)],
+}
+
+// Draw a sprite with its top-left corner at (row, col), clipping anything off-screen.
+// Cells with a black background let the scene's background show through.
+fn blit_sprite(sprite: &Sprite, row: i32, col: i32) {
+    for y in 0..sprite.height {
+        let screen_row = row + y as i32;
+        if !(0..screen_rows() as i32).contains(&screen_row) {
+            continue;
+        }
+        for x in 0..sprite.width {
+            let screen_col = col + x as i32;
+            if !(0..80).contains(&screen_col) {
+                continue;
+            }
+            let (ch, color) = sprite.cells[y * sprite.width + x];
+            if ch != SPRITE_TRANSPARENT {
+                overlay_char_at(ch, screen_row as usize, screen_col as usize, color);
+            }
+        }
+    }
+}
+
+// Deal-with-it shades for the menu: ⌐██─██ (CP437)
+const SWAG_SHADES: Sprite = Sprite {
+    width: 6,
+    height: 2,
+    cells: &[
+        (0x00, BLACK), (0xdc, DARK_GRAY), (0xdc, DARK_GRAY), (0x00, BLACK), (0xdc, DARK_GRAY), (0xdc, DARK_GRAY),
+        (0xa9, WHITE), (0xdb, DARK_GRAY), (0xdb, DARK_GRAY), (0xc4, WHITE), (0xdb, DARK_GRAY), (0xdb, DARK_GRAY),
+    ],
+};
+
+// 5x5 bitmap font for banners: A-Z then 0-9, one byte per row, bit 4 is the leftmost pixel
+static FONT_5X5: [[u8; 5]; 36] = [
+    [0b01110, 0b10001, 0b11111, 0b10001, 0b10001], // A
+    [0b11110, 0b10001, 0b11110, 0b10001, 0b11110], // B
+    [0b01111, 0b10000, 0b10000, 0b10000, 0b01111], // C
+    [0b11110, 0b10001, 0b10001, 0b10001, 0b11110], // D
+    [0b11111, 0b10000, 0b11110, 0b10000, 0b11111], // E
+    [0b11111, 0b10000, 0b11110, 0b10000, 0b10000], // F
+    [0b01111, 0b10000, 0b10011, 0b10001, 0b01111], // G
+    [0b10001, 0b10001, 0b11111, 0b10001, 0b10001], // H
+    [0b11111, 0b00100, 0b00100, 0b00100, 0b11111], // I
+    [0b00111, 0b00010, 0b00010, 0b10010, 0b01100], // J
+    [0b10001, 0b10010, 0b11100, 0b10010, 0b10001], // K
+    [0b10000, 0b10000, 0b10000, 0b10000, 0b11111], // L
+    [0b10001, 0b11011, 0b10101, 0b10001, 0b10001], // M
+    [0b10001, 0b11001, 0b10101, 0b10011, 0b10001], // N
+    [0b01110, 0b10001, 0b10001, 0b10001, 0b01110], // O
+    [0b11110, 0b10001, 0b11110, 0b10000, 0b10000], // P
+    [0b01110, 0b10001, 0b10101, 0b10010, 0b01101], // Q
+    [0b11110, 0b10001, 0b11110, 0b10010, 0b10001], // R
+    [0b01111, 0b10000, 0b01110, 0b00001, 0b11110], // S
+    [0b11111, 0b00100, 0b00100, 0b00100, 0b00100], // T
+    [0b10001, 0b10001, 0b10001, 0b10001, 0b01110], // U
+    [0b10001, 0b10001, 0b10001, 0b01010, 0b00100], // V
+    [0b10001, 0b10001, 0b10101, 0b11011, 0b10001], // W
+    [0b10001, 0b01010, 0b00100, 0b01010, 0b10001], // X
+    [0b10001, 0b01010, 0b00100, 0b00100, 0b00100], // Y
+    [0b11111, 0b00010, 0b00100, 0b01000, 0b11111], // Z
+    [0b01110, 0b10011, 0b10101, 0b11001, 0b01110], // 0
+    [0b00100, 0b01100, 0b00100, 0b00100, 0b01110], // 1
+    [0b11110, 0b00001, 0b01110, 0b10000, 0b11111], // 2
+    [0b11110, 0b00001, 0b00110, 0b00001, 0b11110], // 3
+    [0b10010, 0b10010, 0b11111, 0b00010, 0b00010], // 4
+    [0b11111, 0b10000, 0b11110, 0b00001, 0b11110], // 5
+    [0b01110, 0b10000, 0b11110, 0b10001, 0b01110], // 6
+    [0b11111, 0b00001, 0b00010, 0b00100, 0b00100], // 7
+    [0b01110, 0b10001, 0b01110, 0b10001, 0b01110], // 8
+    [0b01110, 0b10001, 0b01111, 0b00001, 0b01110], // 9
+];
+
+// Each font pixel is drawn two cells wide, since text cells are about twice as tall as wide
+const BIG_PIXEL_WIDTH: usize = 2;
+const BIG_CHAR_ADVANCE: usize = 6 * BIG_PIXEL_WIDTH; // 5 pixels plus a 1 pixel gap
+
+fn big_glyph(ch: u8) -> Option<&'static [u8; 5]> {
+    match ch.to_ascii_uppercase() {
+        c @ b'A'..=b'Z' => Some(&FONT_5X5[(c - b'A') as usize]),
+        c @ b'0'..=b'9' => Some(&FONT_5X5[26 + (c - b'0') as usize]),
+        _ => None, // space and anything unsupported stay blank
+    }
+}
+
+// Draw text 5 rows tall using the banner font. Cells past the screen edge are clipped.
+fn draw_big_text(text: &[u8], row: usize, col: usize, color: ColorCode) {
+    for (i, &ch) in text.iter().enumerate() {
+        let Some(glyph) = big_glyph(ch) else {
+            continue;
+        };
+        let char_col = col + i * BIG_CHAR_ADVANCE;
+        for (y, bits) in glyph.iter().enumerate() {
+            for x in 0..5 {
+                if bits & (0b10000 >> x) != 0 {
+                    for dx in 0..BIG_PIXEL_WIDTH {
+                        write_char_at(0xdb, row + y, char_col + x * BIG_PIXEL_WIDTH + dx, color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// === SCREENSHOT ===
+
+// VGA color numbers in ANSI order: VGA counts blue-green-red, ANSI red-green-blue
+const VGA_TO_ANSI: [u8; 8] = [0, 4, 2, 6, 1, 5, 3, 7];
+
+// Send the text screen out COM1 one row per line, so `-serial file:out.txt` captures
+// what's on it. Anything outside printable ASCII comes out as '.'. With `ansi` the
+// cell colors come along as escape codes (the blink bit is ignored).
+fn dump_screen_to_serial(ansi: bool) {
+    interrupts::without_interrupts(|| {
+        let mut serial = SerialPort(serial::COM1);
+        for row in 0..screen_rows() {
+            let mut last_color = None;
+            for col in 0..80 {
+                let (ch, ColorCode(color)) = read_char_at(row, col);
+                if ansi && last_color != Some(color) {
+                    let fg = VGA_TO_ANSI[(color & 0x07) as usize] + if color & 0x08 != 0 { 90 } else { 30 };
+                    let bg = VGA_TO_ANSI[((color >> 4) & 0x07) as usize] + 40;
+                    let _ = write!(serial, "\x1b[{};{}m", fg, bg);
+                    last_color = Some(color);
+                }
+                let ch = if (0x20..0x7f).contains(&ch) { ch } else { b'.' };
+                let _ = serial.write_char(ch as char);
+            }
+            if ansi {
+                let _ = serial.write_str("\x1b[0m");
+            }
+            let _ = serial.write_str("\n");
+        }
+    });
+}
+
+// === PERF OVERLAY ===
+
+const PERF_WINDOW: usize = 32;
+const PERF_FIELD_WIDTH: usize = 7; // "999 FPS"
+
+#[derive(Clone, Copy)]
+enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+// Where the apps put their FPS overlay; the shell's `fps` command moves it
+static PERF_CORNER: SpinLock<Corner> = SpinLock::new(Corner::TopRight);
+
+// Rolling-average frame rate over the last PERF_WINDOW frames. `frame()` is one tick read
+// and a byte store, so measuring doesn't distort what is being measured.
+struct PerfCounter {
+    frame_ticks: [u8; PERF_WINDOW],
+    next: usize,
+    filled: usize,
+    last_tick: u64,
+}
+
+impl PerfCounter {
+    fn new() -> Self {
+        Self { frame_ticks: [0; PERF_WINDOW], next: 0, filled: 0, last_tick: timer::ticks() }
+    }
+
+    // Call once per rendered frame
+    fn frame(&mut self) {
+        let now = timer::ticks();
+        self.frame_ticks[self.next] = (now - self.last_tick).min(u8::MAX as u64) as u8;
+        self.last_tick = now;
+        self.next = (self.next + 1) % PERF_WINDOW;
+        self.filled = (self.filled + 1).min(PERF_WINDOW);
+    }
+
+    fn fps(&self) -> u32 {
+        let total: u32 = self.frame_ticks[..self.filled].iter().map(|&t| t as u32).sum();
+        if total == 0 {
+            return 0;
+        }
+        (self.filled as u32 * timer::TICK_HZ / total).min(999)
+    }
+
+    fn draw(&self, corner: Corner) {
+        let (row, col) = match corner {
+            Corner::TopLeft => (0, 0),
+            Corner::TopRight => (0, 80 - PERF_FIELD_WIDTH),
+            Corner::BottomLeft => (screen_rows() - 1, 0),
+            Corner::BottomRight => (screen_rows() - 1, 80 - PERF_FIELD_WIDTH),
+        };
+        write_at(b"       ", row, col, BLACK);
+        let digits = write_u32(self.fps(), row, col, WHITE);
+        write_at(b" FPS", row, col + digits, LIGHT_GRAY);
+    }
+}
+
+// === MARQUEE ===
+
+const MARQUEE_GAP: usize = 8; // blank columns between repetitions of the text
+
+// Text scrolling right to left through a window of one row, looping seamlessly.
+// The text can be longer than the window. It's drawn as an overlay, so a black
+// background color keeps whatever background the scene has.
+struct Marquee {
+    text: &'static [u8],
+    row: usize,
+    col: usize,
+    width: usize,
+    color: ColorCode,
+    offset: usize,
+}
+
+impl Marquee {
+    fn new(text: &'static [u8], row: usize, col: usize, width: usize, color: ColorCode) -> Self {
+        Self { text, row, col, width, color, offset: 0 }
+    }
+
+    // Draw the visible window, then scroll one column for next time
+    fn step(&mut self) {
+        let period = self.text.len() + MARQUEE_GAP;
+        for i in 0..self.width {
+            let ch = self.text.get((self.offset + i) % period).copied().unwrap_or(b' ');
+            overlay_char_at(ch, self.row, self.col + i, self.color);
+        }
+        self.offset = (self.offset + 1) % period;
+    }
+}
+
+// === RANDOM NUMBER GENERATOR ===
+
+// Set to `Some(seed)` to replay a run whose seed was logged at launch
+const REPLAY_SEED: Option<u32> = None;
+
+// Where an app's random numbers start. The same seed gives the same run, frame for frame.
+#[derive(Clone, Copy)]
+struct RngConfig {
+    seed: u32,
+}
+
+impl RngConfig {
+    // A fresh seed from the tick counter, unless REPLAY_SEED pins one
+    fn for_launch() -> Self {
+        Self { seed: REPLAY_SEED.unwrap_or(timer::ticks() as u32 ^ 12345) }
+    }
+}
+
+// Marsaglia's xorshift32; each app gets its own so runs can be replayed
+struct Rng {
+    state: u32, // never 0, which xorshift can't get out of
+}
+
+impl Rng {
+    const fn new(seed: u32) -> Self {
+        Self { state: if seed == 0 { 12345 } else { seed } }
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state
+    }
+
+    fn char(&mut self) -> u8 {
+        let chars = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789!@#$%^&*()SWAG";
+        chars[(self.next_u32() % chars.len() as u32) as usize]
+    }
+
+    fn color(&mut self) -> ColorCode {
+        let colors = [LIGHT_GREEN, LIGHT_CYAN, LIGHT_RED, LIGHT_MAGENTA, YELLOW, WHITE, GREEN, CYAN, MAGENTA, BROWN];
+        colors[(self.next_u32() % colors.len() as u32) as usize]
+    }
+}
+
+// Shared generator for everything that doesn't need to be reproducible. `_start`
+// reseeds it, so each boot sparkles differently.
+static DEFAULT_RNG: SpinLock<Rng> = SpinLock::new(Rng::new(12345));
+
+fn seed_rng(seed: u32) {
+    *DEFAULT_RNG.lock() = Rng::new(seed);
+}
+
+fn random() -> u32 {
+    DEFAULT_RNG.lock().next_u32()
+}
+
+fn get_random_char() -> u8 {
+    DEFAULT_RNG.lock().char()
+}
+
+fn get_random_color() -> ColorCode {
+    DEFAULT_RNG.lock().color()
+}
+
+// === FIXED-POINT MATH ===
+
+// `sin_fixed` and `cos_fixed` return this for 1.0
+const SIN_SCALE: i32 = 10000;
+
+// sin(0..=90 degrees) * SIN_SCALE, rounded; the other quadrants are reflections of this one
+static SIN_TABLE: [i16; 91] = [
+    0, 175, 349, 523, 698, 872, 1045, 1219, 1392, 1564,
+    1736, 1908, 2079, 2250, 2419, 2588, 2756, 2924, 3090, 3256,
+    3420, 3584, 3746, 3907, 4067, 4226, 4384, 4540, 4695, 4848,
+    5000, 5150, 5299, 5446, 5592, 5736, 5878, 6018, 6157, 6293,
+    6428, 6561, 6691, 6820, 6947, 7071, 7193, 7314, 7431, 7547,
+    7660, 7771, 7880, 7986, 8090, 8192, 8290, 8387, 8480, 8572,
+    8660, 8746, 8829, 8910, 8988, 9063, 9135, 9205, 9272, 9336,
+    9397, 9455, 9511, 9563, 9613, 9659, 9703, 9744, 9781, 9816,
+    9848, 9877, 9903, 9925, 9945, 9962, 9976, 9986, 9994, 9998,
+    10000,
+];
+
+// Sine of a whole number of degrees, scaled by SIN_SCALE
+fn sin_fixed(deg: i32) -> i32 {
+    let deg = deg.rem_euclid(360) as usize;
+    match deg {
+        0..=90 => SIN_TABLE[deg] as i32,
+        91..=180 => SIN_TABLE[180 - deg] as i32,
+        181..=270 => -(SIN_TABLE[deg - 180] as i32),
+        _ => -(SIN_TABLE[360 - deg] as i32),
+    }
+}
+
+fn cos_fixed(deg: i32) -> i32 {
+    sin_fixed(deg + 90)
+}
+
+// Largest x with x * x <= n, by Newton's method. Starting above the root, each step
+// lands closer to it from above until it stops going down.
+fn isqrt(n: u32) -> u32 {
+    let mut x = n;
+    let mut next = n.div_ceil(2);
+    while next < x {
+        x = next;
+        next = (x + n / x) / 2;
+    }
+    x
+}
+
+// === PANIC HANDLER ===
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    // Straight to COM1 whatever the log level, so `-serial stdio` shows why we died
+    serial_println!("KERNEL PANIC: {}", info);
+    // Under `cargo test` a panic is a failed test: end the run instead of putting on a show
+    if cfg!(test) {
+        qemu::exit_qemu(qemu::QemuExitCode::Failed);
+        interrupts::hlt_loop();
+    }
+    clear_screen();
+    
+    let panic_messages = [
+        b"OH NO! MAXIMUM SWAG OVERLOAD!!!",
+        b"SYSTEM TOO SWAG TO HANDLE!!!!!!",
+        b"SWAG LEVELS: OVER 9000!!!!!!!!!",
+        b"ERROR: NOT ENOUGH SWAG DETECTED",
+        b"PANIC: SWAG BUFFER OVERFLOW!!!!",
+        b"CRITICAL: SWAG CORE MELTDOWN!!!"
+    ];
+    
+    let colors = [LIGHT_RED, YELLOW, LIGHT_GREEN, LIGHT_CYAN, LIGHT_MAGENTA, LIGHT_BLUE];
+    // One screen, drawn once: the flashing is hardware blink, which keeps going while the
+    // CPU sits halted. Which headline comes up depends on when we died.
+    let pick = timer::ticks() as usize;
+    vga::enable_blink(true);
+    
+    let msg = panic_messages[pick % panic_messages.len()];
+    write_at(msg, 2, 24, colors[pick % colors.len()].blinking());
+    
+    write_at(b"KERNEL PANIC at swag_generator():line_MAX", 10, 18, WHITE);
+    write_at(b"Stack trace: SWAG -> MORE_SWAG -> MAXIMUM_SWAG", 12, 16, LIGHT_GRAY);
+    match interrupts::last_fault() {
+        Some(fault) => draw_fault_details(fault, 14),
+        None => write_at(b"Error code: 0xSWAG (cooperative multitasking overload)", 14, 12, LIGHT_RED),
+    }
+    
+    write_at(b" $$$$$$\\  $$\\      $$\\  $$$$$$\\   $$$$$$\\", 16, 20, colors[0]);
+    write_at(b"$$  __$$\\ $$ | $\\  $$ |$$  __$$\\ $$  __$$\\", 17, 19, colors[1]);
+    write_at(b"\\$$$$$$\\  $$ $$ $$\\$$ |$$$$$$$$ |$$ |$$$$\\", 18, 19, colors[2]);
+    write_at(b" \\______/ \\__/     \\__|\\__|  \\__| \\______/", 19, 19, colors[3]);
+    
+    draw_panic_details(info, 20);
+    write_at(b"SYSTEM SWAG OVERLOAD COMPLETE", 22, 25, LIGHT_RED.blinking());
+    write_at(b"RIP SwagOS - Too Swag 4 This World", 23, 22, DARK_GRAY);
+    
+    interrupts::hlt_loop()
+}
+
+// The actual reason behind the swag: panic message on `row`, source location below it
+fn draw_panic_details(info: &PanicInfo, row: usize) {
+    let mut line = ScreenLine::new();
+    let _ = write!(line, "Reason: {}", info.message());
+    write_centered(line.as_bytes(), row, YELLOW);
+    
+    let mut line = ScreenLine::new();
+    let _ = match info.location() {
+        Some(location) => write!(line, "at {}:{}:{}", location.file(), location.line(), location.column()),
+        None => write!(line, "at <location unknown - too swag to trace>"),
+    };
+    write_centered(line.as_bytes(), row + 1, LIGHT_GRAY);
+}
+
+// The exception's error code (and CR2 for page faults) in words, on `row` and the one below
+fn draw_fault_details(fault: interrupts::Fault, row: usize) {
+    use interrupts::*;
+    
+    let mut headline = ScreenLine::new();
+    let mut detail = ScreenLine::new();
+    match fault {
+        Fault::PageFault { address, error_code } => {
+            let _ = write!(headline, "Page fault at {:#x} (error code {:#x})", address, error_code);
+            let access = if error_code & PF_INSTRUCTION != 0 {
+                "instruction fetch from"
+            } else if error_code & PF_WRITE != 0 {
+                "write to"
+            } else {
+                "read from"
+            };
+            let page = if error_code & PF_PRESENT != 0 { "a protected page" } else { "an unmapped page" };
+            let mode = if error_code & PF_USER != 0 { "user" } else { "kernel" };
+            let _ = write!(detail, "{} {} in {} mode", access, page, mode);
+            if error_code & PF_RESERVED != 0 {
+                let _ = write!(detail, ", reserved bit set");
+            }
+        }
+        Fault::GeneralProtection { error_code } => {
+            let _ = write!(headline, "General protection fault (error code {:#x})", error_code);
+            if error_code == 0 {
+                let _ = write!(detail, "not caused by a segment selector");
+            } else {
+                let table = match (error_code >> GP_TABLE_SHIFT) & 0b11 {
+                    0 => "GDT",
+                    2 => "LDT",
+                    _ => "IDT",
+                };
+                let _ = write!(detail, "selector {:#x}: {} entry {}", error_code & 0xffff, table, (error_code & 0xffff) >> GP_INDEX_SHIFT);
+                if error_code & GP_EXTERNAL != 0 {
+                    let _ = write!(detail, ", external event");
+                }
+            }
+        }
+        Fault::Other { vector, error_code } => {
+            let _ = write!(headline, "{} (vector {})", exception_name(vector), vector);
+            let _ = match error_code {
+                Some(error_code) => write!(detail, "error code {:#x}", error_code),
+                None => write!(detail, "no error code"),
+            };
+        }
+    }
+    write_centered(headline.as_bytes(), row, LIGHT_RED);
+    write_centered(detail.as_bytes(), row + 1, LIGHT_RED);
+}
+
+// Recurse until we hit the guard page below the kernel stack. The resulting page fault
+// can't be delivered on the dead stack, so it escalates to a double fault on IST1.
+#[allow(unconditional_recursion)]
+fn overflow_stack(depth: u64) -> u64 {
+    core::hint::black_box(overflow_stack(depth + 1)) + depth // not a tail call
+}
+
+// === APP FRAMEWORK ===
+
+// Whether an app wants another frame
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum AppFlow {
+    Continue,
+    Exit,
+}
+
+// A screen app driven by `run_app`: `update` runs once per frame with the key pressed
+// since the last one (ESC never gets here), then `draw` renders into the backbuffer
+trait App {
+    fn update(&mut self, input: Option<Action>) -> AppFlow;
+    fn draw(&self, buf: &mut Backbuffer);
+}
+
+// Off-screen copy of text memory. `present` only touches cells that changed since the
+// last present, so things drawn straight to VGA (like the background marquee) survive.
+struct Backbuffer {
+    cells: [u16; MAX_SCREEN_CELLS],
+    shown: [u16; MAX_SCREEN_CELLS],
+}
+
+impl Backbuffer {
+    const fn new() -> Self {
+        Self { cells: [BLANK_CELL; MAX_SCREEN_CELLS], shown: [BLANK_CELL; MAX_SCREEN_CELLS] }
+    }
+
+    fn clear(&mut self) {
+        self.cells.fill(BLANK_CELL);
+    }
+
+    fn write_char_at(&mut self, ch: u8, row: usize, col: usize, color: ColorCode) {
+        if row < screen_rows() && col < 80 {
+            self.cells[row * 80 + col] = vga_cell(ch, color);
+        }
+    }
+
+    fn write_at(&mut self, text: &[u8], row: usize, col: usize, color: ColorCode) {
+        self.write_at_clipped(text, row, col, color);
+    }
+
+    // Write text, stopping at the end of the row instead of spilling into the next one
+    fn write_at_clipped(&mut self, text: &[u8], row: usize, col: usize, color: ColorCode) {
+        if row >= screen_rows() || col >= 80 {
+            return;
+        }
+        let visible = text.len().min(80 - col);
+        for (cell, &byte) in self.cells[row * 80 + col..][..visible].iter_mut().zip(text) {
+            *cell = vga_cell(byte, color);
+        }
+    }
+
+    // Ring of 'o's of radius `r` around column `cx`, row `cy`, see `circle_outline`.
+    // Whatever falls off the screen is dropped.
+    fn draw_circle(&mut self, cx: i32, cy: i32, r: i32, color: ColorCode) {
+        circle_outline(r, |dx, dy| {
+            let (row, col) = (cy + dy, cx + dx);
+            if row >= 0 && col >= 0 {
+                self.write_at_clipped(b"o", row as usize, col as usize, color);
+            }
+        });
+    }
+
+    // Write text so its last character lands on `right_col`; anything that
+    // would start left of column 0 is cut off
+    fn write_right(&mut self, text: &[u8], row: usize, right_col: usize, color: ColorCode) {
+        let end = right_col.min(79) + 1;
+        let skip = text.len().saturating_sub(end);
+        self.write_at(&text[skip..], row, end - (text.len() - skip), color);
+    }
+
+    // Copy the frame to the screen in one go, so a half-drawn frame is never visible
+    fn present(&mut self) {
+        let cells = self.cells.iter().zip(self.shown.iter_mut());
+        for (target, (&cell, shown)) in Vga::screen().cells().iter_mut().zip(cells) {
+            if cell != *shown {
+                target.write(cell);
+                *shown = cell;
+            }
+        }
+    }
+}
+
+// Too big for task storage, so every app shares this one
+static mut BACKBUFFER: Backbuffer = Backbuffer::new();
+
+// Clear the screen and hand out the shared backbuffer, blank and in sync with it
+fn fresh_backbuffer() -> &'static mut Backbuffer {
+    let buf = unsafe { &mut *core::ptr::addr_of_mut!(BACKBUFFER) };
+    clear_screen();
+    buf.clear();
+    buf.shown.fill(BLANK_CELL); // matches the cleared screen
+    buf
+}
+
+// The shared +/- handling: speed every animation up or down through the global scale
+fn adjust_speed(input: Option<Action>) {
+    let scale = timer::speed_scale();
+    match input {
+        Some(Action::Faster) => timer::set_speed(scale + SPEED_STEP),
+        Some(Action::Slower) => timer::set_speed(scale.saturating_sub(SPEED_STEP)),
+        _ => return,
+    }
+    log_debug!("speed scale {}/{}", timer::speed_scale(), timer::SPEED_SCALE_ONE);
+}
+
+// Run an app paced by `limiter` until it exits or ESC is pressed
+async fn run_app<A: App>(mut app: A, mut limiter: FrameLimiter) {
+    let buf = fresh_backbuffer();
+    
+    loop {
+        let input = keyboard::poll_key().and_then(keymap::action);
+        if should_stop() {
+            break;
+        }
+        adjust_speed(input);
+        if app.update(input) == AppFlow::Exit {
+            break;
+        }
+        app.draw(buf);
+        buf.present();
+        
+        limiter.wait_for_next_frame().await;
+        yield_now().await;
+    }
+}
+
+// === ASYNC APPLICATIONS ===
+
+const GENERATOR_LINE_MS: u64 = 100; // a new line every this often
+const GENERATOR_HOLD_FRAMES: u32 = 10; // pause on a full screen before starting over
+
+// Fills the screen with `text` one line at a time, then starts over in the next colors
+struct TextScroller<'a> {
+    text: &'a [u8],
+    col: usize, // where each line starts
+    lines: usize,
+    first_color: usize,
+    hold: u32,
+}
+
+impl<'a> TextScroller<'a> {
+    // Lines are centered on `center_col`, but kept on screen; text wider than the
+    // screen starts at column 0 and is cut off on the right
+    fn new(text: &'a [u8], center_col: usize) -> Self {
+        let col = center_col.saturating_sub(text.len() / 2).min(80usize.saturating_sub(text.len()));
+        Self { text, col, lines: 0, first_color: 0, hold: 0 }
+    }
+}
+
+impl App for TextScroller<'_> {
+    fn update(&mut self, _input: Option<Action>) -> AppFlow {
+        if self.lines < screen_rows() {
+            self.lines += 1;
+        } else if self.hold < GENERATOR_HOLD_FRAMES {
+            self.hold += 1;
+        } else {
+            self.first_color += self.lines;
+            self.lines = 0;
+            self.hold = 0;
+        }
+        AppFlow::Continue
+    }
+
+    fn draw(&self, buf: &mut Backbuffer) {
+        buf.clear();
+        for line in 0..self.lines {
+            let color = FIRE.cycle(self.first_color + line);
+            buf.write_at(self.text, line, self.col, color);
+        }
+    }
+}
+
+// The SWAG Generator is `text_scroller(b"SWAG", 40, GENERATOR_LINE_MS)`: a new line
+// every `speed_ms` milliseconds
+async fn text_scroller(text: &[u8], col: usize, speed_ms: u64) {
+    run_app(TextScroller::new(text, col), FrameLimiter::every_ms(speed_ms)).await;
+}
+
+// The row `offset` cells above `base`, wrapping around from the top to the bottom.
+// The matrix clears and draws its trails through this, so both land on the same rows.
+fn wrap_row(base: i32, offset: i32, rows: usize) -> usize {
+    (base - offset).rem_euclid(rows as i32) as usize
+}
+
+// What the matrix rains
+#[derive(Clone, Copy)]
+enum MatrixCharset {
+    Swag,     // letters, digits and symbols, from `Rng::char`
+    Katakana, // the VGA font has none, so CP437 shades, lines and Greek stand in
+    Binary,
+}
+
+const MATRIX_KATAKANA: &[u8] = b"\xb0\xb1\xb2\xb3\xb4\xb5\xb9\xba\xbb\xbc\xc0\xc1\xc2\xc3\xc4\xc5\xe0\xe1\xe2\xe3\xe4\xe5\xe6\xe7\xe8\xe9\xea\xeb\xec\xed\xee\xef";
+
+impl MatrixCharset {
+    fn pick(self, rng: &mut Rng) -> u8 {
+        match self {
+            MatrixCharset::Swag => rng.char(),
+            MatrixCharset::Katakana => MATRIX_KATAKANA[(rng.next_u32() % MATRIX_KATAKANA.len() as u32) as usize],
+            MatrixCharset::Binary => b'0' + (rng.next_u32() % 2) as u8,
+        }
+    }
+}
+
+// Knobs for themed variants of the matrix; the default is the classic look
+#[derive(Clone, Copy)]
+struct MatrixConfig {
+    trail_len: u8, // cells drawn from each column's head upwards
+    reset_chance: u16, // each frame a column starts over from the top with a 1 in this chance
+    charset: MatrixCharset,
+}
+
+impl Default for MatrixConfig {
+    fn default() -> Self {
+        Self { trail_len: 8, reset_chance: 100, charset: MatrixCharset::Swag }
+    }
+}
+
+// Runs in 80x50 for twice the rain, switching back to 80x25 on the way out
+async fn swag_matrix(mut rng: Rng, config: MatrixConfig) {
+    vga::set_text_mode(vga::Mode::T80x50);
+    let _text_mode = vga::RestoreTextMode;
+    let buf = fresh_backbuffer();
+    let rows = screen_rows() as u8;
+    let cols = screen_cols();
+    
+    let mut columns: [u8; 80] = [0; 80];
+    let mut column_speeds: [u8; 80] = [1; 80];
+    
+    // Initialize random speeds and positions
+    for i in 0..cols {
+        column_speeds[i] = ((rng.next_u32() % 3) + 1) as u8;
+        columns[i] = (rng.next_u32() % rows as u32) as u8;
+    }
+    
+    let mut limiter = FrameLimiter::new(TARGET_FPS);
+    
+    loop {
+        let input = keyboard::poll_key().and_then(keymap::action);
+        if should_stop() {
+            break;
+        }
+        adjust_speed(input);
+        
+        // Update each column
+        for col in 0..cols {
+            columns[col] = (columns[col] + column_speeds[col]) % rows;
+            
+            // Clear the old trail
+            for trail in 0..5 {
+                let clear_row = wrap_row(columns[col] as i32, trail, rows as usize);
+                buf.write_char_at(b' ', clear_row, col, BLACK);
+            }
+            
+            // Draw new characters
+            for i in 0..config.trail_len as i32 {
+                let row = wrap_row(columns[col] as i32, i, rows as usize);
+                let char_byte = config.charset.pick(&mut rng);
+                let color = MATRIX_GREEN.fade(i as usize);
+                
+                let final_color = if rng.next_u32().is_multiple_of(20) {
+                    rng.color()
+                } else {
+                    color
+                };
+                
+                buf.write_char_at(char_byte, row, col, final_color);
+            }
+            
+            // Randomly reset column
+            if rng.next_u32().is_multiple_of(config.reset_chance.max(1) as u32) {
+                columns[col] = 0;
+                column_speeds[col] = ((rng.next_u32() % 3) + 1) as u8;
+            }
+        }
+        
+        // Achieved frame rate in the top-right corner; the bottom row belongs to the marquee
+        let mut fps_line = ScreenLine::new();
+        let _ = write!(fps_line, "{:>3} FPS", limiter.fps());
+        buf.write_right(fps_line.as_bytes(), 0, 78, WHITE);
+        buf.present();
+        
+        limiter.wait_for_next_frame().await;
+        yield_now().await;
+    }
+}
+
+// Cells on the screen edge; the corners belong to one side each, so they count once
+fn border_len(rows: usize, cols: usize) -> usize {
+    2 * (rows + cols) - 4
+}
+
+// Cell `pos` steps clockwise around the screen edge, starting at the top-left corner:
+// along the top, down the right side, back along the bottom, then up the left side
+fn border_cell(pos: usize, rows: usize, cols: usize) -> (usize, usize) {
+    let mut pos = pos % border_len(rows, cols);
+    if pos < cols {
+        return (0, pos);
+    }
+    pos -= cols;
+    if pos < rows - 1 {
+        return (pos + 1, cols - 1);
+    }
+    pos -= rows - 1;
+    if pos < cols - 1 {
+        return (rows - 1, cols - 2 - pos);
+    }
+    pos -= cols - 1;
+    (rows - 2 - pos, 0)
+}
+
+// NEW: SWAG HYPNOTIZER - The most mesmerizing thing ever!
+// As a screensaver it leaves the keyboard alone: the menu ends it on any key
+async fn swag_hypnotizer(mut rng: Rng, screensaver: bool) {
+    let rows = screen_rows() as i32;
+    let cols = screen_cols() as i32;
+    let mut time = 0i32;
+    let mut pos_x = cols / 2; // Starting position
+    let mut pos_y = rows / 2;
+    let mut vel_x = 2i32;  // Increased velocity for better bouncing
+    let mut vel_y = 1i32;
+    let swag_texts = [b"SWAG", b"EPIC", b"WOW!", b"MEGA"];
+    let mut text_index = 0;
+    
+    let mut speed = 1i32;
+    let mut palette_index = 0;
+    
+    let buf = fresh_backbuffer();
+    
+    let mut limiter = FrameLimiter::new(TARGET_FPS);
+    
+    loop {
+        // ESC leaves, and the other keys take effect from this frame on
+        let key = if screensaver { None } else { keyboard::poll_key() };
+        if should_stop() {
+            break;
+        }
+        match key.and_then(keymap::action) {
+            Some(Action::Faster) => speed = (speed + 1).min(HYPNOTIZER_MAX_SPEED),
+            Some(Action::Slower) => speed = (speed - 1).max(1),
+            Some(Action::NextPalette) => palette_index = (palette_index + 1) % HYPNOTIZER_PALETTES.len(),
+            _ => {}
+        }
+        let palette = HYPNOTIZER_PALETTES[palette_index];
+        
+        // Each stage of the frame is timed for the debug log
+        let stopwatch = Stopwatch::start();
+        
+        // Clear screen with fading effect
+        for row in 0..rows as usize {
+            for col in 0..cols as usize {
+                if rng.next_u32().is_multiple_of(8) {
+                    buf.write_char_at(b' ', row, col, BLACK);
+                }
+            }
+        }
+        
+        let fade_cycles = stopwatch.elapsed_cycles();
+        
+        // Update position - ensure proper bouncing
+        pos_x += vel_x;
+        pos_y += vel_y;
+        
+        // Bounce off edges with proper boundary checking
+        if pos_x <= 0 {
+            vel_x = vel_x.abs(); // Ensure positive velocity
+            pos_x = 0;
+            text_index = (text_index + 1) % swag_texts.len();
+        } else if pos_x >= cols - 4 {
+            vel_x = -vel_x.abs(); // Ensure negative velocity
+            pos_x = cols - 4;
+            text_index = (text_index + 1) % swag_texts.len();
+        }
+        
+        if pos_y <= 0 {
+            vel_y = vel_y.abs(); // Ensure positive velocity
+            pos_y = 0;
+            text_index = (text_index + 1) % swag_texts.len();
+        } else if pos_y >= rows - 1 {
+            vel_y = -vel_y.abs(); // Ensure negative velocity
+            pos_y = rows - 1;
+            text_index = (text_index + 1) % swag_texts.len();
+        }
+        
+        // Draw the bouncing text with rainbow effects
+        let text = swag_texts[text_index];
+        let color_index = (time / 10) as usize;
+        let base_color = palette.cycle(color_index);
+        
+        // A ring ripples out from the middle, under the text, until it's past the corners
+        let max_radius = isqrt((rows * rows + cols * cols) as u32) as i32 / 2;
+        let ring_color = palette.cycle(color_index + 2);
+        buf.draw_circle(cols / 2, rows / 2, (time / 3) % (max_radius + 1), ring_color);
+        
+        // Draw main text
+        buf.write_at(text, pos_y as usize, pos_x as usize, base_color);
+        
+        // Add a rainbow trail effect
+        for i in 0..5 { // Longer trail
+            let trail_x = pos_x - (vel_x * i);
+            let trail_y = pos_y - (vel_y * i);
+            if trail_x >= 0 && trail_x < cols - 4 && trail_y >= 0 && trail_y < rows {
+                // Each trail segment gets a different rainbow color
+                let trail_color = palette.cycle(color_index + i as usize);
+                buf.write_at(text, trail_y as usize, trail_x as usize, trail_color);
+            }
+        }
+        
+        // Draw corner effects with rainbow colors - simplified!
+        let corner_char = match (time / 10) % 4 {
+            0 => b'\\',
+            1 => b'|',
+            2 => b'/',
+            _ => b'-',
+        };
+        let corner_color = palette.cycle((time / 15) as usize);
+        
+        buf.write_char_at(corner_char, 0, 0, corner_color);
+        let (bottom, right) = (rows as usize - 1, cols as usize - 1);
+        buf.write_char_at(corner_char, 0, right, corner_color);
+        buf.write_char_at(corner_char, bottom, 0, corner_color);
+        buf.write_char_at(corner_char, bottom, right, corner_color);
+        
+        // A rainbow snake chasing itself around the screen border
+        let head = (time / 2) as usize;
+        for i in 0..HYPNOTIZER_BORDER_TRAIL {
+            let (row, col) = border_cell(head + border_len(rows as usize, cols as usize) - i, rows as usize, cols as usize);
+            buf.write_char_at(b'*', row, col, palette.cycle(color_index + i));
+        }
+        let draw_cycles = stopwatch.elapsed_cycles();
+        buf.present();
+        let frame_cycles = stopwatch.elapsed_cycles();
+        log_debug!("hypnotizer frame: fade {} + draw {} + present {} cycles", fade_cycles, draw_cycles - fade_cycles, frame_cycles - draw_cycles);
+        
+        // Update time and phase
+        time = (time + speed) % 3600;
+        
+        limiter.wait_for_next_frame().await;
+        yield_now().await;
+    }
+}
+
+// Logs how much of each second the CPU spent halted. It sleeps between reports, so on
+// an idle menu the number should sit close to 100%.
+async fn idle_monitor() {
+    loop {
+        let start_tick = timer::ticks();
+        let start_halted = HALTED_TICKS.load(Ordering::Relaxed);
+        timer::sleep_ms(1000).await;
+        
+        let elapsed = (timer::ticks() - start_tick).max(1);
+        let halted = HALTED_TICKS.load(Ordering::Relaxed) - start_halted;
+        log_debug!("idle {}% ({} of {} ticks halted)", halted * 100 / elapsed, halted, elapsed);
+    }
+}
+
+// Sparkle requests from the enhancer, as (row, col)
+static SPARKLES: Channel<(usize, usize), 4> = Channel::new();
+
+// Sparkles wait for the rest of the background work before the next one
+const SPARKLE_YIELDS: u32 = 4;
+
+async fn sparkle_painter() {
+    loop {
+        let (row, col) = SPARKLES.recv().await;
+        write_at(b"*", row, col, get_random_color());
+        yield_n(SPARKLE_YIELDS).await;
+    }
+}
+
+// The marquee scrolls along a blue band; overlay_char_at puts the band in with set_bg
+const ENHANCER_MARQUEE_COLOR: ColorCode = ColorCode::new(Color::LightMagenta, Color::Blue);
+
+// Background task that adds some flair
+async fn background_swag_enhancer() {
+    let mut interval = Interval::new_ms(100);
+    // Between the corner sparkles on the bottom row
+    let mut marquee = Marquee::new(b"MAXIMUM SWAG 24/7", screen_rows() - 1, 1, 78, ENHANCER_MARQUEE_COLOR);
+    let mut counter = 0;
+    loop {
+        interval.tick().await;
+        let bottom = screen_rows() - 1; // follows text mode switches
+        marquee.row = bottom;
+        marquee.step();
+        
+        // Add some random swag sparkles to corners
+        if counter % 15 == 0 {
+            for corner in [(0, 0), (0, 79), (bottom, 0), (bottom, 79)] {
+                SPARKLES.send(corner).await;
+            }
+        }
+        
+        counter += 1;
+        yield_now().await;
+    }
+}
+
+// === FIREWORKS ===
+
+// Positions and velocities are in 1/256ths of a cell
+const FIXED_ONE: i32 = 256;
+const GRAVITY: i32 = 8;
+const SPARKS_PER_BURST: i32 = 24;
+const SPARK_LIFETIME: u8 = 30;
+
+#[derive(Clone, Copy)]
+struct Particle {
+    active: bool,
+    rocket: bool, // rockets climb and burst at their apex; sparks fall and fade
+    x: i32,
+    y: i32,
+    vx: i32,
+    vy: i32,
+    age: u8,
+    color: ColorCode,
+}
+
+impl Particle {
+    const INACTIVE: Self = Self { active: false, rocket: false, x: 0, y: 0, vx: 0, vy: 0, age: 0, color: BLACK };
+
+    fn cell(&self) -> Option<(usize, usize)> {
+        let row = self.y / FIXED_ONE;
+        let col = self.x / FIXED_ONE;
+        if self.y >= 0 && self.x >= 0 && row < screen_rows() as i32 && col < 80 {
+            Some((row as usize, col as usize))
+        } else {
+            None
+        }
+    }
+
+    // Sparks shrink and darken as they burn out
+    fn glyph(&self) -> (u8, ColorCode) {
+        if self.rocket {
+            (b'^', WHITE)
+        } else if self.age < SPARK_LIFETIME / 3 {
+            (b'*', self.color)
+        } else if self.age < SPARK_LIFETIME * 2 / 3 {
+            (b'+', self.color)
+        } else {
+            (b'.', ColorCode(self.color.0 & 0x07))
+        }
+    }
+}
+
+// Far too big for a task's 512-byte storage, so the pool is static. Only one show runs at a time.
+static mut PARTICLES: [Particle; 128] = [Particle::INACTIVE; 128];
+
+fn spawn_particle(particles: &mut [Particle], particle: Particle) -> bool {
+    match particles.iter_mut().find(|p| !p.active) {
+        Some(slot) => {
+            *slot = particle;
+            true
+        }
+        None => false,
+    }
+}
+
+// Spread sparks evenly around a circle. Cells are about twice as tall as wide, so x speed is doubled.
+fn burst(rng: &mut Rng, particles: &mut [Particle], x: i32, y: i32, color: ColorCode) {
+    for i in 0..SPARKS_PER_BURST {
+        let angle = i * 360 / SPARKS_PER_BURST;
+        let speed = 60 + (rng.next_u32() % 40) as i32;
+        let spark = Particle {
+            active: true,
+            rocket: false,
+            x,
+            y,
+            vx: cos_fixed(angle) * speed * 2 / SIN_SCALE,
+            vy: sin_fixed(angle) * speed / SIN_SCALE,
+            age: 0,
+            color,
+        };
+        if !spawn_particle(particles, spark) {
+            break;
+        }
+    }
+}
+
+async fn fireworks(mut rng: Rng) {
+    let particles = unsafe { &mut *core::ptr::addr_of_mut!(PARTICLES) };
+    particles.fill(Particle::INACTIVE);
+    clear_screen();
+    
+    let mut limiter = FrameLimiter::new(TARGET_FPS);
+    let mut perf = PerfCounter::new();
+    
+    loop {
+        let input = keyboard::poll_key().and_then(keymap::action);
+        if should_stop() {
+            break;
+        }
+        adjust_speed(input);
+        
+        // Launch a rocket from a random column now and then
+        if rng.next_u32().is_multiple_of(12) {
+            let rocket = Particle {
+                active: true,
+                rocket: true,
+                x: (5 + rng.next_u32() % 70) as i32 * FIXED_ONE,
+                y: (screen_rows() - 1) as i32 * FIXED_ONE,
+                vx: (rng.next_u32() % 65) as i32 - 32,
+                vy: -(230 + (rng.next_u32() % 60) as i32),
+                age: 0,
+                color: rng.color(),
+            };
+            spawn_particle(particles, rocket);
+        }
+        
+        for i in 0..particles.len() {
+            let mut particle = particles[i];
+            if !particle.active {
+                continue;
             }
             
-            // Draw new characters
-            for i in 0..8 {
-                let row = if columns[col] >= i { 
-                    columns[col] - i 
-                } else { 
-                    25 + columns[col] - i 
-                };
-                if row < 25 {
-                    let char_byte = get_random_char();
-                    let color = if i == 0 { 
-                        0x0f 
-                    } else if i < 3 {
-                        0x0a 
-                    } else {
-                        0x02 
-                    };
-                    
-                    let final_color = if random() % 20 == 0 {
-                        get_random_color()
+            if let Some((row, col)) = particle.cell() {
+                write_char_at(b' ', row, col, BLACK);
+            }
+            
+            particle.x += particle.vx;
+            particle.y += particle.vy;
+            particle.vy += GRAVITY;
+            particle.age = particle.age.saturating_add(1);
+            
+            if particle.rocket && particle.vy >= 0 {
+                // Apex reached - free the rocket's slot first so the burst can use it
+                particles[i] = Particle::INACTIVE;
+                burst(&mut rng, particles, particle.x, particle.y, particle.color);
+                continue;
+            }
+            
+            let burnt_out = !particle.rocket && particle.age >= SPARK_LIFETIME;
+            match particle.cell() {
+                Some((row, col)) if !burnt_out => {
+                    let (glyph, color) = particle.glyph();
+                    write_char_at(glyph, row, col, color);
+                    particles[i] = particle;
+                }
+                _ => particles[i] = Particle::INACTIVE,
+            }
+        }
+        
+        perf.frame();
+        perf.draw(*PERF_CORNER.lock());
+        
+        limiter.wait_for_next_frame().await;
+        yield_now().await;
+    }
+}
+
+// === SNAKE ===
+
+const SNAKE_TICK_MS: u64 = 100;
+const SNAKE_MAX_LEN: usize = 256;
+const SNAKE_START_LEN: usize = 4;
+const SNAKE_EAT_BEEP: (u32, u64) = (880, 40); // Hz, ms
+const SNAKE_DEATH_BEEP: (u32, u64) = (110, 400);
+const SNAKE_CRASH_FLASHES: u32 = 4;
+
+// Ring of body cells as (row, col): the head sits at `head` and the rest trail behind it.
+// 512 bytes is all of a task's storage, so the body is static like PARTICLES.
+static mut SNAKE_BODY: [(u8, u8); SNAKE_MAX_LEN] = [(0, 0); SNAKE_MAX_LEN];
+
+// Inside the border, which leaves the bottom screen row to the marquee
+fn snake_field() -> (usize, usize) {
+    (screen_rows() - 2, 79) // last row and column that are still border
+}
+
+fn snake_cell(body: &[(u8, u8); SNAKE_MAX_LEN], head: usize, i: usize) -> (usize, usize) {
+    let (row, col) = body[(head + SNAKE_MAX_LEN - i) % SNAKE_MAX_LEN];
+    (row as usize, col as usize)
+}
+
+// Food goes on a random free cell inside the border
+fn place_food(rng: &mut Rng, body: &[(u8, u8); SNAKE_MAX_LEN], head: usize, len: usize) -> (usize, usize) {
+    let (bottom, right) = snake_field();
+    loop {
+        let row = 1 + (rng.next_u32() as usize % (bottom - 1));
+        let col = 1 + (rng.next_u32() as usize % (right - 1));
+        if !(0..len).any(|i| snake_cell(body, head, i) == (row, col)) {
+            write_char_at(b'*', row, col, LIGHT_RED);
+            return (row, col);
+        }
+    }
+}
+
+fn draw_snake_score(score: u8) {
+    let mut line = ScreenLine::new();
+    let _ = write!(line, " SCORE {} ", score);
+    write_at(line.as_bytes(), 0, 3, YELLOW);
+}
+
+// Best score so far, from the table in CMOS, mirroring the score on the other side of
+// the top border
+fn draw_snake_best(right: usize) {
+    let mut line = ScreenLine::new();
+    let _ = write!(line, " BEST {} ", HighScores::load().scores[0]);
+    write_at(line.as_bytes(), 0, right - 2 - line.as_bytes().len(), YELLOW);
+}
+
+// Arrows steer, food makes the snake longer, and hitting the border or itself ends the
+// game. The score goes into the high score table.
+async fn swag_snake(mut rng: Rng) {
+    let body = unsafe { &mut *core::ptr::addr_of_mut!(SNAKE_BODY) };
+    let (bottom, right) = snake_field();
+    clear_screen();
+    draw_box(0, 0, bottom + 1, right + 1, LIGHT_CYAN);
+    
+    // Start in the middle heading right, tail to the left
+    let (start_row, start_col) = (bottom / 2, right / 2);
+    for (i, cell) in body[..SNAKE_START_LEN].iter_mut().enumerate() {
+        let col = start_col + 1 + i - SNAKE_START_LEN;
+        *cell = (start_row as u8, col as u8);
+        write_char_at(b'o', start_row, col, LIGHT_GREEN);
+    }
+    let mut head = SNAKE_START_LEN - 1;
+    let mut len = SNAKE_START_LEN;
+    let mut direction = (0i32, 1i32);
+    let mut score = 0u8;
+    let mut food = place_food(&mut rng, body, head, len);
+    draw_snake_score(score);
+    draw_snake_best(right);
+    
+    loop {
+        // Every key since the last step counts, but the snake can't turn back on itself
+        while let Some(key) = keyboard::poll_key() {
+            let turn = match keymap::action(key) {
+                Some(Action::Up) => (-1, 0),
+                Some(Action::Down) => (1, 0),
+                Some(Action::Left) => (0, -1),
+                Some(Action::Right) => (0, 1),
+                _ => continue,
+            };
+            if turn != (-direction.0, -direction.1) {
+                direction = turn;
+            }
+        }
+        if should_stop() {
+            return;
+        }
+        
+        let (row, col) = snake_cell(body, head, 0);
+        let next = ((row as i32 + direction.0) as usize, (col as i32 + direction.1) as usize);
+        let eating = next == food;
+        // The tail moves out of the way this step unless the snake is growing
+        let solid = if eating { len } else { len - 1 };
+        let hit_wall = next.0 == 0 || next.0 >= bottom || next.1 == 0 || next.1 >= right;
+        if hit_wall || (0..solid).any(|i| snake_cell(body, head, i) == next) {
+            break;
+        }
+        
+        if eating && len < SNAKE_MAX_LEN {
+            len += 1;
+        } else {
+            let (tail_row, tail_col) = snake_cell(body, head, len - 1);
+            write_char_at(b' ', tail_row, tail_col, LIGHT_GRAY);
+        }
+        write_char_at(b'o', row, col, LIGHT_GREEN);
+        head = (head + 1) % SNAKE_MAX_LEN;
+        body[head] = (next.0 as u8, next.1 as u8);
+        write_char_at(b'@', next.0, next.1, YELLOW);
+        
+        if eating {
+            score = score.saturating_add(1);
+            draw_snake_score(score);
+            food = place_food(&mut rng, body, head, len);
+            // Beeps during the step's sleep, so eating doesn't slow the snake down
+            let (hz, ms) = SNAKE_EAT_BEEP;
+            join(speaker::beep_for_ms(hz, ms), timer::sleep_ms(SNAKE_TICK_MS)).await;
+        } else {
+            timer::sleep_ms(SNAKE_TICK_MS).await;
+        }
+    }
+    
+    {
+        let mut line = ScreenLine::new();
+        let _ = write!(line, " GAME OVER - score {} ", score);
+        write_centered(line.as_bytes(), bottom / 2, WHITE_ON_RED);
+    }
+    if HighScores::submit(score) {
+        write_centered(b" NEW HIGH SCORE! ", bottom / 2 + 1, YELLOW);
+    }
+    // The death beep and the flashing crash play over the result, and keys mashed while
+    // dying can't skip it before the second is up
+    let (hz, ms) = SNAKE_DEATH_BEEP;
+    let (crash_row, crash_col) = snake_cell(body, head, 0);
+    join3(speaker::beep_for_ms(hz, ms), flash_crash(crash_row, crash_col), timer::sleep_ms(1000)).await;
+    keyboard::flush();
+    keyboard::next_key().await;
+}
+
+// The head blinks between itself and an X where the snake crashed
+async fn flash_crash(row: usize, col: usize) {
+    for i in 0..SNAKE_CRASH_FLASHES * 2 {
+        if i.is_multiple_of(2) {
+            write_char_at(b'@', row, col, YELLOW);
+        } else {
+            write_char_at(b'X', row, col, LIGHT_RED);
+        }
+        timer::sleep_ms(100).await;
+    }
+}
+
+// === PONG ===
+
+const PONG_FPS: u32 = 20; // the ball moves one cell a frame
+const PONG_WINNING_SCORE: u8 = 5;
+const PADDLE_HEIGHT: usize = 4;
+const PADDLE_GLYPH: u8 = 0xdb;
+const BALL_GLYPH: u8 = b'O';
+
+struct Paddle {
+    col: usize,
+    top: usize,
+}
+
+impl Paddle {
+    fn covers(&self, row: i32) -> bool {
+        (self.top as i32..(self.top + PADDLE_HEIGHT) as i32).contains(&row)
+    }
+
+    // Where the ball hits decides where it goes: the ends send it off at an angle,
+    // the middle sends it back flat
+    fn deflection(&self, row: i32) -> i32 {
+        match row - self.top as i32 {
+            0 => -1,
+            offset if offset == PADDLE_HEIGHT as i32 - 1 => 1,
+            _ => 0,
+        }
+    }
+
+    fn draw(&self, glyph: u8) {
+        fill_region(self.top, self.col, 1, PADDLE_HEIGHT, glyph, WHITE);
+    }
+}
+
+fn draw_pong_score(left: u8, right: u8) {
+    let mut line = ScreenLine::new();
+    let _ = write!(line, "W/S  {}  -  {}  arrows", left, right);
+    fill_region(0, 0, 80, 1, b' ', LIGHT_GRAY);
+    write_centered(line.as_bytes(), 0, YELLOW);
+}
+
+// Ball back in the middle of the field, heading along `direction` at a random angle
+fn serve(first_row: i32, last_row: i32, direction: i32) -> ((i32, i32), (i32, i32)) {
+    let angle = (random() % 3) as i32 - 1;
+    (((first_row + last_row) / 2, 40), (angle, direction))
+}
+
+// Two players on one keyboard: W/S move the left paddle, the arrows the right one.
+// First to PONG_WINNING_SCORE wins.
+async fn swag_pong() {
+    let bottom = screen_rows() - 2; // bottom wall, above the marquee
+    let (first_row, last_row) = (2, bottom as i32 - 1); // between the walls
+    let paddle_top = (first_row as usize + bottom - PADDLE_HEIGHT) / 2;
+    let mut left = Paddle { col: 2, top: paddle_top };
+    let mut right = Paddle { col: 77, top: paddle_top };
+    let (mut left_score, mut right_score) = (0u8, 0u8);
+    let ((mut row, mut col), (mut row_step, mut col_step)) = serve(first_row, last_row, 1);
+    
+    clear_screen();
+    fill_region(1, 0, 80, 1, BOX_HORIZONTAL, LIGHT_CYAN);
+    fill_region(bottom, 0, 80, 1, BOX_HORIZONTAL, LIGHT_CYAN);
+    draw_pong_score(left_score, right_score);
+    
+    let mut limiter = FrameLimiter::new(PONG_FPS);
+    while left_score < PONG_WINNING_SCORE && right_score < PONG_WINNING_SCORE {
+        while keyboard::poll_key().is_some() {} // ESC shows up in `should_stop` below
+        // Paddles follow whatever is held this frame, so both players can move at once
+        let controls = [
+            (&mut left, keyboard::SCANCODE_W, keyboard::SCANCODE_S),
+            (&mut right, keyboard::SCANCODE_UP, keyboard::SCANCODE_DOWN),
+        ];
+        for (paddle, up, down) in controls {
+            let step = keyboard::is_key_down(down) as i32 - keyboard::is_key_down(up) as i32;
+            if step != 0 {
+                paddle.draw(b' ');
+                paddle.top = (paddle.top as i32 + step).clamp(first_row, last_row + 1 - PADDLE_HEIGHT as i32) as usize;
+            }
+        }
+        if should_stop() {
+            return;
+        }
+        
+        write_char_at(b' ', row as usize, col as usize, LIGHT_GRAY);
+        if let Some(paddle) = [&left, &right].into_iter().find(|paddle| col + col_step == paddle.col as i32 && paddle.covers(row + row_step)) {
+            row_step = paddle.deflection(row + row_step);
+            col_step = -col_step;
+        }
+        if !(first_row..=last_row).contains(&(row + row_step)) {
+            row_step = -row_step;
+        }
+        row += row_step;
+        col += col_step;
+        
+        // Past a paddle and into the side of the screen: a point, and the player who
+        // missed gets the next serve coming at them
+        if col <= 0 || col >= 79 {
+            let toward = if col <= 0 { -1 } else { 1 };
+            if toward < 0 {
+                right_score += 1;
+            } else {
+                left_score += 1;
+            }
+            draw_pong_score(left_score, right_score);
+            ((row, col), (row_step, col_step)) = serve(first_row, last_row, toward);
+        }
+        
+        left.draw(PADDLE_GLYPH);
+        right.draw(PADDLE_GLYPH);
+        write_char_at(BALL_GLYPH, row as usize, col as usize, WHITE);
+        limiter.wait_for_next_frame().await;
+    }
+    
+    clear_screen();
+    draw_big_text(b"SWAG", 6, 17, YELLOW);
+    let winner: &[u8] = if left_score > right_score { b" LEFT PLAYER WINS! " } else { b" RIGHT PLAYER WINS! " };
+    write_centered(winner, 13, WHITE_ON_RED);
+    let mut line = ScreenLine::new();
+    let _ = write!(line, "{} - {}", left_score, right_score);
+    write_centered(line.as_bytes(), 15, YELLOW);
+    // The winner's points go in the same table as snake's, like any other game score
+    if HighScores::submit(left_score.max(right_score)) {
+        write_centered(b" NEW HIGH SCORE! ", 16, YELLOW);
+    }
+    timer::sleep_ms(1000).await; // same as snake: mashed keys shouldn't skip the result
+    keyboard::flush();
+    keyboard::next_key().await;
+}
+
+// === EVENT LOG VIEWER ===
+
+const EVENT_VIEWER_ROWS: usize = 20;
+
+// Shows the most recent scheduler and input events, newest at the bottom
+struct EventLogViewer;
+
+impl App for EventLogViewer {
+    fn update(&mut self, _input: Option<Action>) -> AppFlow {
+        AppFlow::Continue
+    }
+
+    fn draw(&self, buf: &mut Backbuffer) {
+        let mut entries = [event_log::Entry { tick: 0, event: Event::SpawnFailed }; EVENT_VIEWER_ROWS];
+        let count = event_log::latest(&mut entries);
+        
+        buf.clear();
+        buf.write_at(b"SWAG EVENT LOG - ESC to exit", 0, 26, YELLOW);
+        buf.write_at(b"    TICK  EVENT", 2, 2, DARK_GRAY);
+        for (i, entry) in entries[..count].iter().enumerate() {
+            let mut line = ScreenLine::new();
+            let _ = write!(line, "{:>8}  ", entry.tick);
+            let _ = match entry.event {
+                Event::KeyPressed(code) => write!(line, "key pressed   {:#04x}", code),
+                Event::TaskSpawned(slot) => write!(line, "task spawned  slot {}", slot),
+                Event::TaskFinished(slot) => write!(line, "task finished slot {}", slot),
+                Event::TaskCancelled(slot) => write!(line, "task cancel   slot {}", slot),
+                Event::SpawnFailed => write!(line, "spawn failed  no free slot"),
+            };
+            buf.write_at(line.as_bytes(), 3 + i, 2, LIGHT_GRAY);
+        }
+    }
+}
+
+// === MEMORY TEST ===
+
+const MEMTEST_MAX_BYTES: usize = 4 * 1024 * 1024; // keep a run under a few seconds
+const MEMTEST_CHUNK_WORDS: usize = 512; // 4 KiB between yields
+const MEMTEST_BAR_ROW: usize = 10;
+const MEMTEST_BAR_COL: usize = 10;
+const MEMTEST_BAR_WIDTH: usize = 60;
+
+#[derive(Clone, Copy)]
+enum MemPattern {
+    Fill(u64),
+    Address, // every word holds its own address, catches aliased address lines
+}
+
+impl MemPattern {
+    fn value(self, addr: usize) -> u64 {
+        match self {
+            MemPattern::Fill(value) => value,
+            MemPattern::Address => addr as u64,
+        }
+    }
+}
+
+const MEMTEST_PATTERNS: [(&[u8], MemPattern); 3] = [
+    (b"Pattern 0xAA   ", MemPattern::Fill(0xaaaa_aaaa_aaaa_aaaa)),
+    (b"Pattern 0x55   ", MemPattern::Fill(0x5555_5555_5555_5555)),
+    (b"Address in addr", MemPattern::Address),
+];
+
+// Largest usable RAM region as a (virtual address, length) pair. The bootloader marks the
+// kernel, its stack, the page tables and the boot info as separate types, so none of
+// those are in a usable region.
+// The region `memtest_region` picked at boot, for every menu `Executor::run` starts
+static MEMTEST_REGION: SpinLock<Option<(usize, usize)>> = SpinLock::new(None);
+
+fn memtest_region(boot_info: &BootInfo) -> Option<(usize, usize)> {
+    boot_info
+        .memory_map
+        .iter()
+        .filter(|region| region.region_type == MemoryRegionType::Usable)
+        .max_by_key(|region| region.range.end_addr() - region.range.start_addr())
+        .map(|region| {
+            let start = boot_info.physical_memory_offset + region.range.start_addr();
+            let len = (region.range.end_addr() - region.range.start_addr()) as usize;
+            (start as usize, len.min(MEMTEST_MAX_BYTES))
+        })
+}
+
+// Write each pattern over the whole region, then read it all back and count bad words
+async fn memtest(start: usize, len: usize) {
+    let words = len / 8;
+    let chunks = words.div_ceil(MEMTEST_CHUNK_WORDS);
+    let total_steps = (chunks * 2 * MEMTEST_PATTERNS.len()).max(1);
+    let mut step = 0;
+    let mut passed: u32 = 0;
+    let mut failed: u32 = 0;
+    
+    let mut line = ScreenLine::new();
+    let _ = write!(line, "Testing {} KiB at {:#x}", len / 1024, start);
+    write_at(b"SWAG MEMTEST", 6, 34, YELLOW);
+    write_centered(line.as_bytes(), 8, LIGHT_GRAY);
+    fill_region(MEMTEST_BAR_ROW, MEMTEST_BAR_COL, MEMTEST_BAR_WIDTH, 1, 0xb0, DARK_GRAY);
+    write_at(b"Passed:", 14, 28, LIGHT_GREEN);
+    write_at(b"Failed:", 15, 28, LIGHT_RED);
+    
+    for (label, pattern) in MEMTEST_PATTERNS {
+        write_at(label, 12, 32, WHITE);
+        
+        for verify in [false, true] {
+            for chunk in 0..chunks {
+                if should_stop() {
+                    write_at(b"Aborted", 17, 36, LIGHT_RED);
+                    return;
+                }
+                
+                let first = chunk * MEMTEST_CHUNK_WORDS;
+                for i in first..(first + MEMTEST_CHUNK_WORDS).min(words) {
+                    let addr = start + i * 8;
+                    let expected = pattern.value(addr);
+                    if !verify {
+                        unsafe { core::ptr::write_volatile(addr as *mut u64, expected) };
+                    } else if unsafe { core::ptr::read_volatile(addr as *const u64) } == expected {
+                        passed += 1;
                     } else {
-                        color
-                    };
-                    
-                    write_at(&[char_byte], row as usize, col, final_color);
+                        failed += 1;
+                    }
                 }
+                
+                step += 1;
+                fill_region(MEMTEST_BAR_ROW, MEMTEST_BAR_COL, step * MEMTEST_BAR_WIDTH / total_steps, 1, 0xdb, LIGHT_GREEN);
+                write_u32(passed, 14, 36, WHITE);
+                write_u32(failed, 15, 36, WHITE);
+                yield_now().await;
             }
-            
-            // Randomly reset column
-            if random() % 100 == 0 {
-                columns[col] = 0;
-                column_speeds[col] = ((random() % 3) + 1) as u8;
+        }
+    }
+    
+    if failed == 0 {
+        write_at(b"PASS - your RAM is swag", 17, 28, LIGHT_GREEN);
+    } else {
+        write_at(b"FAIL - bad cells found!", 17, 28, LIGHT_RED);
+        log_error!("memtest: {} of {} words read back wrong", failed, passed + failed);
+    }
+    write_at(b"Press ESC to return", 19, 30, DARK_GRAY);
+    while !should_stop() {
+        keyboard::next_key().await;
+    }
+}
+
+// === TEXT PLASMA ===
+
+// CP437 shade blocks, lightest to solid
+const PLASMA_SHADES: [u8; 4] = [0xb0, 0xb1, 0xb2, 0xdb];
+
+// The classic plasma in text mode: three sine waves summed per cell, the sum picking
+// a rainbow color and a shade block within it
+struct Plasma {
+    t: i32,
+}
+
+impl App for Plasma {
+    fn update(&mut self, _input: Option<Action>) -> AppFlow {
+        self.t = (self.t + 3) % 360;
+        AppFlow::Continue
+    }
+
+    // The waves are tabulated per column, row and diagonal so the cell loop only adds
+    fn draw(&self, buf: &mut Backbuffer) {
+        let rows = screen_rows();
+        let mut columns = [0i32; 80];
+        let mut row_terms = [0i32; vga::MAX_ROWS];
+        let mut diagonals = [0i32; 80 + vga::MAX_ROWS];
+        for (x, term) in columns.iter_mut().enumerate() {
+            *term = sin_fixed(x as i32 * 9 + self.t);
+        }
+        for (y, term) in row_terms[..rows].iter_mut().enumerate() {
+            *term = sin_fixed(y as i32 * 22 + self.t * 2); // cells are about twice as tall as wide
+        }
+        for (d, term) in diagonals.iter_mut().enumerate() {
+            *term = sin_fixed(d as i32 * 7 + self.t * 3);
+        }
+        
+        let levels = (RAINBOW.colors.len() * PLASMA_SHADES.len()) as i32;
+        for (y, row_term) in row_terms[..rows].iter().enumerate() {
+            for (x, column_term) in columns.iter().enumerate() {
+                let sum = column_term + row_term + diagonals[x + y]; // +-3 * SIN_SCALE
+                let level = ((sum + 3 * SIN_SCALE) * levels / (6 * SIN_SCALE + 1)) as usize;
+                let shade = PLASMA_SHADES[level % PLASMA_SHADES.len()];
+                buf.write_char_at(shade, y, x, RAINBOW.cycle(level / PLASMA_SHADES.len()));
             }
         }
-        
-        delay(50_000).await;
-        yield_now().await;
     }
 }
 
-// NEW: SWAG HYPNOTIZER - The most mesmerizing thing ever!
-async fn swag_hypnotizer() {
-    let mut time = 0i32;
-    let mut pos_x = 40i32; // Starting position
-    let mut pos_y = 12i32;
-    let mut vel_x = 2i32;  // Increased velocity for better bouncing
-    let mut vel_y = 1i32;
-    let swag_texts = [b"SWAG", b"EPIC", b"WOW!", b"MEGA"];
-    let mut text_index = 0;
-    
-    // Rainbow colors (VGA colors)
-    let rainbow_colors = [
-        0x0c, // Light Red
-        0x0e, // Yellow
-        0x0a, // Light Green
-        0x0b, // Light Cyan
-        0x09, // Light Blue
-        0x0d, // Light Magenta
-    ];
+async fn plasma() {
+    run_app(Plasma { t: 0 }, FrameLimiter::new(TARGET_FPS)).await;
+}
+
+// === MODE 13H PLASMA ===
+
+// Smooth 256-entry color cycle for the DAC: three sine waves a third of a turn apart
+fn load_plasma_palette() {
+    for i in 0..256 {
+        let deg = i * 360 / 256;
+        let channel = |offset: i32| (32 + sin_fixed(deg + offset) * 31 / SIN_SCALE) as u8;
+        vga::set_palette_color(i as u8, channel(0), channel(120), channel(240));
+    }
+}
+
+// One frame: the sum of a column wave, a row wave and a diagonal wave picks each pixel's
+// palette entry. The waves are tabulated first so the pixel loop is just additions.
+fn draw_plasma13(t: i32) {
+    const W: usize = vga::MODE13_WIDTH;
+    const H: usize = vga::MODE13_HEIGHT;
+    let mut columns = [0i32; W];
+    let mut rows = [0i32; H];
+    let mut diagonals = [0i32; W + H];
+    for (x, term) in columns.iter_mut().enumerate() {
+        *term = sin_fixed(x as i32 * 2 + t);
+    }
+    for (y, term) in rows.iter_mut().enumerate() {
+        *term = sin_fixed(y as i32 * 3 + t * 2);
+    }
+    for (d, term) in diagonals.iter_mut().enumerate() {
+        *term = sin_fixed(d as i32 + t * 3);
+    }
+    
+    for (y, row_term) in rows.iter().enumerate() {
+        for (x, column_term) in columns.iter().enumerate() {
+            let sum = column_term + row_term + diagonals[x + y]; // +-3 * SIN_SCALE
+            vga::put_pixel13(x, y, ((sum + 3 * SIN_SCALE) * 255 / (6 * SIN_SCALE)) as u8);
+        }
+    }
+}
+
+// Plasma in real pixels, then back to text mode for the menu
+async fn plasma13() {
+    vga::set_mode13();
+    let _text_mode = vga::RestoreTextMode;
+    load_plasma_palette();
+    
+    let mut limiter = FrameLimiter::new(TARGET_FPS);
+    let mut t = 0;
+    loop {
+        let input = keyboard::poll_key().and_then(keymap::action);
+        if should_stop() {
+            break;
+        }
+        adjust_speed(input);
+        draw_plasma13(t);
+        t = (t + 4) % 360;
+        limiter.wait_for_next_frame().await;
+    }
+}
+
+// === BENCHMARK ===
+
+const BENCH_ITERATIONS: u32 = 100_000;
+const BENCH_ROW_ITERATIONS: u32 = 2_000; // each one rewrites a whole row
+const BENCH_SCRATCH_ROW: usize = 20;
+const BENCH_ROW_TEXT: [u8; 80] = [b'#'; 80];
+
+// Average TSC cycles per call of `op`, which gets the iteration number
+fn cycles_per_op(iterations: u32, mut op: impl FnMut(u32)) -> u64 {
+    let stopwatch = Stopwatch::start();
+    for i in 0..iterations {
+        op(i);
+    }
+    stopwatch.elapsed_cycles() / iterations as u64
+}
+
+fn show_bench_result(row: usize, name: &str, cycles: u64) {
+    write_at(name.as_bytes(), row, 12, WHITE);
+    let digits = write_u32(cycles.min(u32::MAX as u64) as u32, row, 40, LIGHT_GREEN);
+    write_at(b" cycles/op", row, 40 + digits, LIGHT_GRAY);
+    log_info!("bench {}: {} cycles/op", name, cycles);
+}
+
+// Times the hot helpers so performance changes come with before/after numbers
+async fn bench() {
+    use core::hint::black_box;
     
     clear_screen();
+    write_centered(b"SWAG Benchmark", 1, YELLOW);
+    write_centered(b"TSC cycles per operation, lower is better", 2, DARK_GRAY);
+    
+    let cycles = cycles_per_op(BENCH_ITERATIONS, |i| {
+        black_box(sin_fixed(black_box(i as i32)));
+    });
+    show_bench_result(5, "sin_fixed", cycles);
+    yield_now().await;
+    
+    let cycles = cycles_per_op(BENCH_ITERATIONS, |_| {
+        black_box(random());
+    });
+    show_bench_result(6, "random", cycles);
+    yield_now().await;
     
+    let cycles = cycles_per_op(BENCH_ITERATIONS, |_| {
+        black_box(get_random_char());
+    });
+    show_bench_result(7, "get_random_char", cycles);
+    yield_now().await;
+    
+    let cycles = cycles_per_op(BENCH_ROW_ITERATIONS, |i| {
+        write_at(&BENCH_ROW_TEXT, BENCH_SCRATCH_ROW, 0, ColorCode((i % 15 + 1) as u8));
+    });
+    fill_region(BENCH_SCRATCH_ROW, 0, 80, 1, b' ', LIGHT_GRAY);
+    show_bench_result(8, "write_at (80 columns)", cycles);
+    
+    write_centered(b"Press ESC to return", 22, DARK_GRAY);
+    while !should_stop() {
+        keyboard::next_key().await;
+    }
+}
+
+// === CLOCK ===
+
+// The RTC only counts whole seconds, so there's no point redrawing more often
+const CLOCK_REFRESH_MS: u64 = 1000;
+
+async fn swag_clock() {
+    clear_screen();
+    write_centered(b"SWAG Clock", 8, YELLOW);
+    draw_box(10, 30, 5, 20, LIGHT_CYAN);
+    write_centered(b"Press ESC to return", 22, DARK_GRAY);
     loop {
-        // Check for ESC key
-        if let Some(scan_code) = read_keyboard() {
-            if scan_code == KEY_ESC {
-                break;
+        let (hours, minutes, seconds) = cmos::read_rtc();
+        let mut line = ScreenLine::new();
+        let _ = write!(line, "{:02}:{:02}:{:02}", hours, minutes, seconds);
+        write_centered(line.as_bytes(), 12, WHITE);
+        
+        timer::sleep_ms(CLOCK_REFRESH_MS).await;
+        if should_stop() {
+            return;
+        }
+    }
+}
+
+// === SHELL ===
+
+const SHELL_LINE_MAX: usize = 64;
+const SHELL_PROMPT: &[u8] = b"swag> ";
+const SHELL_BANNER_COLOR: ColorCode = WHITE_ON_BLUE;
+const SHELL_OUTPUT_COLOR: ColorCode = LIGHT_GRAY;
+
+static mut SHELL_LINE: [u8; SHELL_LINE_MAX] = [0; SHELL_LINE_MAX];
+
+const INPUT_LINE_MAX: usize = 64;
+const INPUT_PROMPT_COLOR: ColorCode = YELLOW;
+const INPUT_TEXT_COLOR: ColorCode = WHITE;
+
+// Line editor anywhere on screen: `prompt` at the start of `row`, then up to `max`
+// characters typed after it with the hardware cursor following along. Input past the
+// limit, which is also capped by INPUT_LINE_MAX and the right edge, is ignored. Enter
+// hands back the buffer and how much of it was typed. So does ESC, whose stop request
+// the caller sees through `should_stop()`.
+async fn read_line(prompt: &[u8], row: usize, max: usize) -> ([u8; INPUT_LINE_MAX], usize) {
+    let mut line = [0u8; INPUT_LINE_MAX];
+    let mut len = 0;
+    fill_region(row, 0, screen_cols(), 1, b' ', INPUT_TEXT_COLOR);
+    let start = write_at_clipped(prompt, row, 0, INPUT_PROMPT_COLOR);
+    // The cursor needs a cell after the last character too
+    let max = max.min(INPUT_LINE_MAX).min(screen_cols().saturating_sub(start + 1));
+    loop {
+        vga::set_cursor(row, start + len);
+        vga::enable_underline_cursor();
+        let key = keyboard::next_key().await;
+        vga::disable_cursor();
+        if should_stop() {
+            return (line, len);
+        }
+        match key {
+            keyboard::SCANCODE_ENTER => return (line, len),
+            keyboard::SCANCODE_BACKSPACE => {
+                if len > 0 {
+                    len -= 1;
+                    write_char_at(b' ', row, start + len, INPUT_TEXT_COLOR);
+                }
+            }
+            _ => {
+                if let Some(ch) = keyboard::typed_char(key)
+                    && len < max
+                {
+                    line[len] = ch as u8; // the keymap is all ASCII
+                    write_char_at(ch as u8, row, start + len, INPUT_TEXT_COLOR);
+                    len += 1;
+                }
             }
         }
-        
-        // Clear screen with fading effect
-        for row in 0..25 {
-            for col in 0..80 {
-                if random() % 8 == 0 {
-                    write_char_at(b' ', row, col, 0x00);
+    }
+}
+
+const SHELL_HELP: &[u8] = b"commands:
+  clear      - clear the screen
+  matrix     - run the SWAG Matrix (matrix katakana, matrix binary)
+  hypno      - run the SWAG Hypnotizer
+  clock      - show the time from the RTC
+  about      - about SwagOS
+  reboot     - restart the machine
+  quit       - end the QEMU run (needs its isa-debug-exit device)
+  screenshot - copy the screen to the serial port
+  repeat D R - held keys repeat after D ticks, then every R
+  keys wasd  - WASD moves like the arrows (keys us undoes it)
+  debug      - turn debug logging to COM1 on or off
+  fps CORNER - FPS counter corner (fps top-left, fps bottom-right, ...)
+  help       - this list
+ESC leaves the shell
+";
+
+// The space-separated words of a command line
+fn words(line: &[u8]) -> impl Iterator<Item = &[u8]> {
+    line.split(|&ch| ch == b' ').filter(|word| !word.is_empty())
+}
+
+// A command argument as a decimal number
+fn parse_number(word: &[u8]) -> Option<u64> {
+    core::str::from_utf8(word).ok()?.parse().ok()
+}
+
+// A prompt that runs apps by name, as an alternative to the number-key menu
+async fn shell() {
+    // A static, so launching an app from here doesn't also carry the line in the task's storage
+    let line = unsafe { &mut *core::ptr::addr_of_mut!(SHELL_LINE) };
+    console().clear();
+    console().put_bytes(b"SwagOS shell - type help for commands\n", SHELL_BANNER_COLOR);
+
+    loop {
+        // The typed copy is gone before any app runs, so it doesn't take task storage
+        let row = console().row; // not inside the call, or the lock would be held while it waits
+        let len = {
+            let (typed, len) = read_line(SHELL_PROMPT, row, SHELL_LINE_MAX).await;
+            line[..len].copy_from_slice(&typed[..len]);
+            len
+        };
+        if should_stop() {
+            return;
+        }
+        console().newline();
+        // Arguments are split out again where they're used, so no iterator is kept
+        // around while an app runs
+        let command = words(&line[..len]).next().unwrap_or(b"");
+
+        console().color = SHELL_OUTPUT_COLOR;
+        match command {
+            b"" => {}
+            b"clear" => console().clear(),
+            b"matrix" => {
+                let charset = match words(&line[..len]).nth(1) {
+                    Some(b"katakana") => MatrixCharset::Katakana,
+                    Some(b"binary") => MatrixCharset::Binary,
+                    _ => MatrixCharset::Swag,
+                };
+                swag_matrix(launch_rng("matrix"), MatrixConfig { charset, ..MatrixConfig::default() }).await;
+                clear_stop(); // ESC ends the app, not the shell
+                keyboard::flush();
+                console().clear();
+            }
+            b"hypno" => {
+                clear_screen();
+                let _ = timeout(timer::ms_to_ticks(HYPNOTIZER_TIMEOUT_MS), swag_hypnotizer(launch_rng("hypnotizer"), false)).await;
+                clear_stop(); // ESC ends the app, not the shell
+                keyboard::flush();
+                console().clear();
+            }
+            b"clock" => {
+                swag_clock().await;
+                clear_stop(); // ESC ends the app, not the shell
+                keyboard::flush();
+                console().clear();
+            }
+            b"about" => {
+                let uptime = timer::uptime_ms() / 1000;
+                println!("SwagOS v0.0.1 - The Most Swag Operating System Ever");
+                println!("up {}m {}s, PIT at {} Hz", uptime / 60, uptime % 60, timer::TICK_HZ);
+            }
+            b"reboot" => {
+                log_info!("reboot requested from the shell");
+                keyboard::reboot();
+            }
+            b"quit" => {
+                log_info!("quit requested from the shell");
+                qemu::exit_qemu(qemu::QemuExitCode::Success);
+                // Still here, so there's no exit device to write to
+                console().put_bytes(b"not running under QEMU with isa-debug-exit\n", SHELL_OUTPUT_COLOR);
+            }
+            b"screenshot" => {
+                dump_screen_to_serial(true);
+                console().put_bytes(b"screen sent to COM1\n", SHELL_OUTPUT_COLOR);
+            }
+            b"repeat" => match (words(&line[..len]).nth(1).and_then(parse_number), words(&line[..len]).nth(2).and_then(parse_number)) {
+                (Some(delay), Some(rate)) => {
+                    keyboard::set_repeat(delay, rate);
+                    println!("held keys repeat after {} ticks, then every {}", delay, rate.max(1));
+                }
+                _ => console().put_bytes(b"usage: repeat <delay> <rate>, both in ticks\n", SHELL_OUTPUT_COLOR),
+            },
+            b"fps" => {
+                let corner = match words(&line[..len]).nth(1) {
+                    Some(b"top-left") => Some(Corner::TopLeft),
+                    Some(b"top-right") => Some(Corner::TopRight),
+                    Some(b"bottom-left") => Some(Corner::BottomLeft),
+                    Some(b"bottom-right") => Some(Corner::BottomRight),
+                    _ => None,
+                };
+                match corner {
+                    Some(corner) => {
+                        *PERF_CORNER.lock() = corner;
+                        console().put_bytes(b"FPS counter moved\n", SHELL_OUTPUT_COLOR);
+                    }
+                    None => console().put_bytes(b"usage: fps top-left|top-right|bottom-left|bottom-right\n", SHELL_OUTPUT_COLOR),
+                }
+            }
+            b"debug" => {
+                // Debug output, like the hypnotizer's frame timings, only goes to COM1
+                let on = !log::enabled(log::Level::Debug);
+                log::set_log_level(if on { log::Level::Debug } else { log::Level::Info });
+                println!("debug logging {}", if on { "on, see COM1" } else { "off" });
+            }
+            b"keys" => match words(&line[..len]).nth(1) {
+                Some(b"wasd") => {
+                    // S loses its menu shortcut to the shell while it's Down
+                    for (key, action) in [(0x11, Action::Up), (0x1e, Action::Left), (0x1f, Action::Down), (0x20, Action::Right)] {
+                        keymap::set_action(key, action);
+                    }
+                    console().put_bytes(b"WASD moves like the arrows\n", SHELL_OUTPUT_COLOR);
                 }
+                Some(b"us") => {
+                    keymap::set_keymap(&keymap::US_LAYOUT);
+                    console().put_bytes(b"default US keys\n", SHELL_OUTPUT_COLOR);
+                }
+                _ => console().put_bytes(b"usage: keys wasd|us\n", SHELL_OUTPUT_COLOR),
+            },
+            b"help" => console().put_bytes(SHELL_HELP, SHELL_OUTPUT_COLOR),
+            _ => {
+                console().put_bytes(b"unknown command: ", SHELL_OUTPUT_COLOR);
+                console().put_bytes(command, SHELL_OUTPUT_COLOR);
+                console().put_bytes(b" (try help)\n", SHELL_OUTPUT_COLOR);
             }
         }
-        
-        // Update position - ensure proper bouncing
-        pos_x += vel_x;
-        pos_y += vel_y;
-        
-        // Bounce off edges with proper boundary checking
-        if pos_x <= 0 {
-            vel_x = vel_x.abs(); // Ensure positive velocity
-            pos_x = 0;
-            text_index = (text_index + 1) % swag_texts.len();
-        } else if pos_x >= 76 {
-            vel_x = -vel_x.abs(); // Ensure negative velocity
-            pos_x = 76;
-            text_index = (text_index + 1) % swag_texts.len();
+    }
+}
+
+// === HIGH SCORES ===
+
+// Top three scores kept in CMOS NVRAM so they survive a reboot. 0x10-0x2f is covered by
+// the BIOS's own checksum, so the table lives near the end.
+const HIGH_SCORE_OFFSET: u8 = 0x38;
+const HIGH_SCORE_COUNT: usize = 3;
+const HIGH_SCORE_CHECKSUM_SEED: u8 = 0xa5; // all-zero NVRAM must not look valid
+
+struct HighScores {
+    scores: [u8; HIGH_SCORE_COUNT], // best first
+}
+
+impl HighScores {
+    // Read the table back, starting over with zeros if the checksum doesn't match
+    fn load() -> Self {
+        let mut scores = [0; HIGH_SCORE_COUNT];
+        for (i, score) in scores.iter_mut().enumerate() {
+            *score = cmos::nvram_read(HIGH_SCORE_OFFSET + i as u8);
         }
+        let stored = cmos::nvram_read(HIGH_SCORE_OFFSET + HIGH_SCORE_COUNT as u8);
         
-        if pos_y <= 0 {
-            vel_y = vel_y.abs(); // Ensure positive velocity
-            pos_y = 0;
-            text_index = (text_index + 1) % swag_texts.len();
-        } else if pos_y >= 24 {
-            vel_y = -vel_y.abs(); // Ensure negative velocity
-            pos_y = 24;
-            text_index = (text_index + 1) % swag_texts.len();
+        let table = Self { scores };
+        if stored == table.checksum() {
+            table
+        } else {
+            let table = Self { scores: [0; HIGH_SCORE_COUNT] };
+            table.save();
+            table
         }
-        
-        // Draw the bouncing text with rainbow effects
-        let text = swag_texts[text_index];
-        let color_index = ((time / 10) % rainbow_colors.len() as i32) as usize;
-        let base_color = rainbow_colors[color_index];
-        
-        // Draw main text
-        write_at(text, pos_y as usize, pos_x as usize, base_color);
-        
-        // Add a rainbow trail effect
-        for i in 0..5 { // Longer trail
-            let trail_x = pos_x - (vel_x * i);
-            let trail_y = pos_y - (vel_y * i);
-            if trail_x >= 0 && trail_x < 76 && trail_y >= 0 && trail_y < 25 {
-                // Each trail segment gets a different rainbow color
-                let trail_color_index = ((color_index as i32 + i as i32) % rainbow_colors.len() as i32) as usize;
-                let trail_color = rainbow_colors[trail_color_index];
-                write_at(text, trail_y as usize, trail_x as usize, trail_color);
-            }
+    }
+
+    fn save(&self) {
+        for (i, &score) in self.scores.iter().enumerate() {
+            cmos::nvram_write(HIGH_SCORE_OFFSET + i as u8, score);
         }
-        
-        // Draw corner effects with rainbow colors - simplified!
-        let corner_char = match (time / 10) % 4 {
-            0 => b'\\',
-            1 => b'|',
-            2 => b'/',
-            _ => b'-',
+        cmos::nvram_write(HIGH_SCORE_OFFSET + HIGH_SCORE_COUNT as u8, self.checksum());
+    }
+
+    fn checksum(&self) -> u8 {
+        self.scores.iter().fold(HIGH_SCORE_CHECKSUM_SEED, |sum, &score| sum.wrapping_add(score))
+    }
+
+    // Put `score` in the table if it beats an entry, saving right away. Returns whether it did.
+    fn submit(score: u8) -> bool {
+        let mut table = Self::load();
+        let Some(rank) = table.scores.iter().position(|&best| score > best) else {
+            return false;
         };
-        let corner_color = rainbow_colors[(time / 15) as usize % rainbow_colors.len()];
-        
-        write_char_at(corner_char, 0, 0, corner_color);
-        write_char_at(corner_char, 0, 79, corner_color);
-        write_char_at(corner_char, 24, 0, corner_color);
-        write_char_at(corner_char, 24, 79, corner_color);
+        table.scores.copy_within(rank..HIGH_SCORE_COUNT - 1, rank + 1);
+        table.scores[rank] = score;
+        table.save();
+        true
+    }
+}
+
+const MENU_OPTIONS: u8 = 10; // the 10th is picked with 0
+
+const MENU_ITEMS: [(&[u8], ColorCode); MENU_OPTIONS as usize] = [
+    (b"1) SWAG Generator", LIGHT_GREEN),
+    (b"2) Panic!!! (now with $wag)", LIGHT_RED),
+    (b"3) SWAG Matrix", LIGHT_CYAN),
+    (b"4) SWAG Hypnotizer (truly mesmerizing)", LIGHT_MAGENTA), // NEW!
+    (b"5) SWAG Snake (arrow keys)", LIGHT_GREEN),
+    (b"6) SWAG Fireworks", YELLOW),
+    (b"7) SWAG Memtest", LIGHT_GRAY),
+    (b"8) SWAG Plasma (320x200 pixels!)", LIGHT_MAGENTA),
+    (b"9) SWAG Plasma (text mode classic)", LIGHT_CYAN),
+    (b"0) SWAG Pong (W/S vs arrow keys)", YELLOW),
+];
+
+// The options stay left-aligned with each other; the block as a whole is centered.
+// The highlight bar is as wide as the widest option plus a space either side.
+fn draw_menu_option(index: u8, highlighted: bool) {
+    let widest = MENU_ITEMS.iter().map(|(text, _)| text.len()).max().unwrap_or(0);
+    let col = (80 - widest) / 2;
+    let (text, color) = MENU_ITEMS[index as usize];
+    let color = if highlighted { color.inverted() } else { color };
+    let row = 11 + index as usize;
+    fill_region(row, col - 1, widest + 2, 1, b' ', color);
+    write_at(text, row, col, color);
+}
+
+fn show_menu(selected: u8) {
+    clear_screen();
+    
+    draw_big_text(b"SWAG", 0, 17, YELLOW);
+    write_centered(b"========== SwagOS v0.0.1 ==========", 5, YELLOW);
+    write_centered(b"The Most Swag Operating System Ever", 6, LIGHT_GREEN);
+    blit_sprite(&SWAG_SHADES, 8, 37);
+    write_centered(b"Choose your destiny:", 10, WHITE);
+    
+    for i in 0..MENU_OPTIONS {
+        draw_menu_option(i, i == selected);
+    }
+    
+    let scores = HighScores::load().scores;
+    let mut high_scores = ScreenLine::new();
+    let _ = write!(high_scores, "High scores: {} / {} / {}", scores[0], scores[1], scores[2]);
+    write_centered(high_scores.as_bytes(), 21, LIGHT_CYAN);
+    
+    write_centered(b"Arrows + Enter or number keys (ESC in apps to return, F11 pause, F12 force quit)", 22, DARK_GRAY);
+    write_centered(b"Powered by: Cooperative Multitasking", 23, LIGHT_MAGENTA);
+}
+
+// Fresh generator for an app, logging the seed so a good run can be replayed
+fn launch_rng(app: &str) -> Rng {
+    let config = RngConfig::for_launch();
+    log_info!("{} seed {}", app, config.seed);
+    Rng::new(config.seed)
+}
+
+// Wait for a key on the menu screen, showing the mouse cursor as an inverted cell.
+// Returns `None` once the keyboard has been idle for the screensaver timeout. Idle
+// time counts from the last key or from when this started waiting, whichever is later,
+// so a menu that just came back from an app that ended on its own isn't idle already.
+async fn next_menu_key() -> Option<keyboard::Key> {
+    let idle_limit = timer::ms_to_ticks(SCREENSAVER_TIMEOUT_MS);
+    let waiting_since = timer::ticks();
+    let mut cursor: Option<(usize, usize, u8, ColorCode)> = None; // position plus the cell it covers
+    loop {
+        let key = keyboard::poll_key();
+        let idle = keyboard::ticks_since_last_key().min(timer::ticks() - waiting_since);
+        if key.is_some() || idle >= idle_limit {
+            if let Some((row, col, ch, color)) = cursor {
+                write_char_at(ch, row, col, color);
+            }
+            return key;
+        }
         
-        // Update time and phase
-        time = (time + 1) % 3600;
+        let (x, y, _) = mouse::mouse_state();
+        let (row, col) = (y as usize, x as usize);
+        if !matches!(cursor, Some((r, c, _, _)) if (r, c) == (row, col)) {
+            if let Some((r, c, ch, color)) = cursor {
+                write_char_at(ch, r, c, color);
+            }
+            let (ch, color) = read_char_at(row, col);
+            let inverted = ColorCode(color.0.rotate_left(4));
+            write_char_at(ch, row, col, if inverted == color { BLACK_ON_LIGHT_GRAY } else { inverted });
+            cursor = Some((row, col, ch, color));
+        }
         
-        delay(80_000).await; // Adjusted for better movement speed
-        yield_now().await;
+        // Sleep until the next tick; mouse and keyboard IRQs just get picked up then
+        timer::sleep_ticks(1).await;
     }
 }
 
-// Background task that adds some flair
-async fn background_swag_enhancer() {
-    let mut counter = 0;
+// Click when a menu entry is picked
+const MENU_BEEP_HZ: u32 = 1000;
+const MENU_BEEP_MS: u64 = 30;
+
+// The menu is a task too; the chosen app runs inline until it returns
+async fn menu(memtest_region: Option<(usize, usize)>) {
+    // Index into `MENU_ITEMS`; stays put across apps so Enter relaunches the last one
+    let mut selected = 0u8;
     loop {
-        delay(2_000_000).await;
+        // Keys typed into the last app shouldn't pick the next one
+        keyboard::flush();
+        transition::fade_in(transition::DEFAULT_TICKS, || show_menu(selected)).await;
         
-        // Add some random swag sparkles to corners
-        if counter % 3 == 0 {
-            write_at(b"*", 0, 0, get_random_color());
-            write_at(b"*", 0, 79, get_random_color());
-            write_at(b"*", 24, 0, get_random_color());
-            write_at(b"*", 24, 79, get_random_color());
+        loop {
+            let Some(key) = next_menu_key().await else {
+                // Idle: screensaver until any key, then back to a fresh menu. The key
+                // wins the race and the hypnotizer is dropped mid-frame. An ESC pressed
+                // on the menu earlier mustn't end it straight away.
+                clear_stop();
+                select2(keyboard::next_key(), swag_hypnotizer(launch_rng("screensaver"), true)).await;
+                break;
+            };
+            let action = match keymap::action(key) {
+                Some(step @ (Action::Up | Action::Down)) => {
+                    // Only the two lines that changed get redrawn
+                    draw_menu_option(selected, false);
+                    let step = if step == Action::Up { MENU_OPTIONS - 1 } else { 1 };
+                    selected = (selected + step) % MENU_OPTIONS;
+                    draw_menu_option(selected, true);
+                    continue;
+                }
+                _ if key == keyboard::SCANCODE_ENTER => Some(Action::MenuSelect(selected + 1)),
+                action => action,
+            };
+            // Stale ESC presses from the menu screen must not stop the app right away, and
+            // keys held down on the menu don't count as held in the app
+            clear_stop();
+            keyboard::flush();
+            if let Some(Action::MenuSelect(n @ 1..=MENU_OPTIONS)) = action {
+                selected = n - 1;
+                speaker::beep_for_ms(MENU_BEEP_HZ, MENU_BEEP_MS).await;
+            }
+            match action {
+                Some(Action::MenuSelect(1)) => {
+                    text_scroller(b"SWAG", 40, GENERATOR_LINE_MS).await;
+                }
+                Some(Action::MenuSelect(2)) => {
+                    panic!("Maximum SWAG achieved!");
+                }
+                Some(Action::StackOverflow) => {
+                    overflow_stack(0);
+                }
+                Some(Action::EventLog) => {
+                    run_app(EventLogViewer, FrameLimiter::new(10)).await;
+                }
+                Some(Action::Shell) => {
+                    shell().await;
+                }
+                Some(Action::Bench) => {
+                    bench().await;
+                }
+                Some(Action::Clock) => {
+                    swag_clock().await;
+                }
+                Some(Action::MenuSelect(3)) => {
+                    clear_screen();
+                    swag_matrix(launch_rng("matrix"), MatrixConfig::default()).await;
+                }
+                Some(Action::MenuSelect(4)) => { // NEW HYPNOTIZER OPTION!
+                    clear_screen();
+                    let _ = timeout(timer::ms_to_ticks(HYPNOTIZER_TIMEOUT_MS), swag_hypnotizer(launch_rng("hypnotizer"), false)).await;
+                }
+                Some(Action::MenuSelect(5)) => {
+                    swag_snake(launch_rng("snake")).await;
+                }
+                Some(Action::MenuSelect(6)) => {
+                    fireworks(launch_rng("fireworks")).await;
+                }
+                Some(Action::MenuSelect(7)) => {
+                    let Some((start, len)) = memtest_region else {
+                        continue; // no usable RAM reported
+                    };
+                    clear_screen();
+                    memtest(start, len).await;
+                }
+                Some(Action::MenuSelect(8)) => {
+                    plasma13().await;
+                }
+                Some(Action::MenuSelect(9)) => {
+                    plasma().await;
+                }
+                Some(Action::MenuSelect(10)) => {
+                    swag_pong().await;
+                }
+                _ => continue,
+            }
+            break;
         }
-        
-        counter += 1;
-        yield_now().await;
+        transition::fade_out(transition::DEFAULT_TICKS).await;
     }
 }
 
-fn show_menu() {
-    clear_screen();
-    
-    let title = b"========== SwagOS v0.0.1 ==========";
-    let subtitle = b"The Most Swag Operating System Ever";
-    let menu_header = b"Choose your destiny:";
-    let option1 = b"1) SWAG Generator";
-    let option2 = b"2) Panic!!! (now with $wag)";
-    let option3 = b"3) SWAG Matrix";
-    let option4 = b"4) SWAG Hypnotizer (truly mesmerizing)"; // NEW!
-    let instruction = b"Press the number key... (ESC in apps to return)";
-    let tech = b"Powered by: Cooperative Multitasking";
-    
-    write_at(title, 5, 22, 0x0e);
-    write_at(subtitle, 7, 22, 0x0a);
-    write_at(menu_header, 12, 30, 0x0f);
-    write_at(option1, 14, 32, 0x0a);
-    write_at(option2, 15, 32, 0x0c);
-    write_at(option3, 16, 32, 0x0b);
-    write_at(option4, 17, 32, 0x0d); // NEW!
-    write_at(instruction, 20, 20, 0x08);
-    write_at(tech, 22, 22, 0x0d);
+// How long the "all slots taken" notice stays up
+const CAPACITY_NOTICE_MS: u64 = 1500;
+
+// Spawn a background task, and if every slot is taken say so on screen instead of silently
+// dropping it
+fn spawn_background_or_warn<F: Future<Output = ()> + 'static>(executor: &mut KernelExecutor, future: F) {
+    if let Err(error) = executor.spawn_background(future) {
+        warn_spawn_failed(core::any::type_name::<F>(), error);
+    }
+}
+
+// Runs outside of any task, so it waits out the notice by halting between ticks
+fn warn_spawn_failed(name: &str, error: SpawnError) {
+    log_warn!("couldn't spawn {}: {:?}", name, error);
+    write_centered(b" SWAG CAPACITY REACHED ", 12, WHITE_ON_RED);
+    let until = timer::ticks() + timer::ms_to_ticks(CAPACITY_NOTICE_MS);
+    while timer::ticks() < until {
+        interrupts::halt();
+    }
+    fill_region(12, 0, 80, 1, b' ', LIGHT_GRAY);
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn _start() -> ! {
-    let mut executor = Executor::new();
+pub extern "C" fn _start(boot_info: &'static BootInfo) -> ! {
+    SerialPort(serial::COM1).init();
+    gdt::init();
+    interrupts::init();
+    mouse::init();
+    timer::init_pit(timer::TICK_HZ);
+    interrupts::enable();
+    vga::disable_cursor(); // only the shell wants it
+    log_info!("SwagOS v0.0.1 up, PIT at {} Hz", timer::TICK_HZ);
+    // The PIT has barely ticked this early, but the TSC's low bits differ every boot
+    seed_rng(timer::rdtsc() as u32);
     
-    // Spawn the background swag enhancer
-    executor.spawn(background_swag_enhancer());
+    #[cfg(test)]
+    test_main();
     
-    loop {
-        show_menu();
-        
-        // Wait for user input
-        let mut waiting_for_input = true;
-        while waiting_for_input {
-            if let Some(scan_code) = read_keyboard() {
-                match scan_code {
-                    KEY_1 => {
-                        clear_screen();
-                        // Run SWAG generator cooperatively with background task
-                        executor.spawn(swag_generator());
-                        
-                        // Run executor until SWAG generator completes
-                        loop {
-                            executor.run_step();
-                            // Check if main task (SWAG gen) is still running - exclude background task
-                            let mut has_main_task = false;
-                            for i in 1..executor.tasks.len() { // Skip slot 0 (background task)
-                                if executor.tasks[i].is_active() {
-                                    has_main_task = true;
-                                    break;
-                                }
-                            }
-                            if !has_main_task {
-                                break;
-                            }
-                        }
-                        waiting_for_input = false;
-                    }
-                    KEY_2 => {
-                        panic!("Maximum SWAG achieved!");
-                    }
-                    KEY_3 => {
-                        clear_screen();
-                        executor.spawn(swag_matrix());
-                        
-                        // Run executor until matrix completes
-                        loop {
-                            executor.run_step();
-                            let mut has_main_task = false;
-                            for i in 1..executor.tasks.len() { // Skip slot 0 (background task)
-                                if executor.tasks[i].is_active() {
-                                    has_main_task = true;
-                                    break;
-                                }
-                            }
-                            if !has_main_task {
-                                break;
-                            }
-                        }
-                        waiting_for_input = false;
-                    }
-                    KEY_4 => { // NEW HYPNOTIZER OPTION!
-                        clear_screen();
-                        executor.spawn(swag_hypnotizer());
-                        
-                        // Run executor until hypnotizer completes
-                        loop {
-                            executor.run_step();
-                            let mut has_main_task = false;
-                            for i in 1..executor.tasks.len() { // Skip slot 0 (background task)
-                                if executor.tasks[i].is_active() {
-                                    has_main_task = true;
-                                    break;
-                                }
-                            }
-                            if !has_main_task {
-                                break;
-                            }
-                        }
-                        waiting_for_input = false;
-                    }
-                    _ => {}
-                }
+    let mut executor = KernelExecutor::new();
+    
+    // The marquee and idle stats run in the background: they keep going while F11 has the
+    // menu and apps paused, and wait their turn while an app is busy
+    spawn_background_or_warn(&mut executor, background_swag_enhancer());
+    spawn_background_or_warn(&mut executor, sparkle_painter());
+    spawn_background_or_warn(&mut executor, idle_monitor());
+    
+    *MEMTEST_REGION.lock() = memtest_region(boot_info);
+    watchdog::start(watchdog::DEFAULT_TIMEOUT_MS);
+    executor.run()
+}
+
+// === TESTS ===
+
+// A `#[test_case]` function, which reports its name and result on COM1. A failing test
+// panics, and the panic handler ends the QEMU run from there.
+#[cfg(test)]
+trait Testable {
+    fn run(&self);
+}
+
+#[cfg(test)]
+impl<T: Fn()> Testable for T {
+    fn run(&self) {
+        serial_print!("{}... ", core::any::type_name::<T>());
+        self();
+        serial_println!("[ok]");
+    }
+}
+
+// Runs once the kernel is initialized, in place of the executor
+#[cfg(test)]
+fn test_runner(tests: &[&dyn Testable]) -> ! {
+    serial_println!("running {} tests", tests.len());
+    for test in tests {
+        test.run();
+    }
+    qemu::exit_qemu(qemu::QemuExitCode::Success);
+    interrupts::hlt_loop()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Poll `future` until it finishes, for futures that only wait on their own wakeups
+    fn poll_to_end<F: Future>(future: F) -> F::Output {
+        let mut future = core::pin::pin!(future);
+        let mut context = Context::from_waker(Waker::noop());
+        for _ in 0..100 {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
             }
-            
-            // Keep running background tasks even while waiting for input
-            executor.run_step();
+        }
+        panic!("future still pending after 100 polls");
+    }
+
+    #[test_case]
+    fn yield_n_is_pending_count_times() {
+        let mut future = yield_n(3);
+        let mut context = Context::from_waker(Waker::noop());
+        let mut pending = 0;
+        while Pin::new(&mut future).poll(&mut context).is_pending() {
+            pending += 1;
+        }
+        assert_eq!(pending, 3);
+        assert!(Pin::new(&mut yield_n(0)).poll(&mut context).is_ready());
+    }
+
+    #[test_case]
+    fn join_waits_for_both() {
+        let slow = async {
+            yield_now().await;
+            1
+        };
+        assert_eq!(poll_to_end(join(slow, async { 2 })), (1, 2));
+    }
+
+    #[test_case]
+    fn join3_waits_for_all() {
+        let slow = async {
+            yield_now().await;
+            yield_now().await;
+            3
+        };
+        assert_eq!(poll_to_end(join3(async { 1 }, slow, async { 2 })), (1, 3, 2));
+    }
+
+    #[test_case]
+    fn select2_returns_the_first_to_finish() {
+        let slow = async {
+            yield_now().await;
+            1
+        };
+        assert_eq!(poll_to_end(select2(slow, async { 'b' })), Either::Second('b'));
+        assert_eq!(poll_to_end(select2(async { 1 }, async { 'b' })), Either::First(1));
+    }
+
+    // The loser is dropped before the winner's output comes back
+    #[test_case]
+    fn select2_drops_the_loser() {
+        static DROPPED: AtomicBool = AtomicBool::new(false);
+        struct Guard;
+        impl Drop for Guard {
+            fn drop(&mut self) {
+                DROPPED.store(true, Ordering::Relaxed);
+            }
+        }
+
+        let loser = async {
+            let _guard = Guard;
+            core::future::pending::<()>().await;
+        };
+        let winner = async {
+            yield_now().await;
+            DROPPED.load(Ordering::Relaxed)
+        };
+        assert_eq!(poll_to_end(select2(loser, winner)), Either::Second(false));
+        assert!(DROPPED.load(Ordering::Relaxed));
+    }
+
+    #[test_case]
+    fn timeout_passes_the_result_through() {
+        assert_eq!(poll_to_end(timeout(0, async { 5 })), Ok(5));
+    }
+
+    #[test_case]
+    fn timeout_gives_up_at_the_deadline() {
+        assert_eq!(poll_to_end(timeout(0, core::future::pending::<()>())), Err(timer::Timeout));
+    }
+
+    #[test_case]
+    fn sin_fixed_known_angles() {
+        assert_eq!(sin_fixed(0), 0);
+        assert_eq!(sin_fixed(90), SIN_SCALE);
+        assert!((sin_fixed(30) - SIN_SCALE / 2).abs() <= 1);
+        assert_eq!(sin_fixed(270), -SIN_SCALE);
+        assert_eq!(sin_fixed(-90), sin_fixed(270));
+        assert_eq!(cos_fixed(0), SIN_SCALE);
+    }
+
+    #[test_case]
+    fn isqrt_rounds_down() {
+        assert_eq!(isqrt(0), 0);
+        assert_eq!(isqrt(1), 1);
+        assert_eq!(isqrt(2), 1);
+        assert_eq!(isqrt(17), 4);
+        assert_eq!(isqrt(24), 4);
+        assert_eq!(isqrt(25), 5);
+        assert_eq!(isqrt(10000), 100);
+        assert_eq!(isqrt(u32::MAX), 65535);
+    }
+
+    #[test_case]
+    fn circle_outline_has_no_gaps() {
+        let mut grid = [[b'.'; 7]; 7];
+        circle_outline(3, |dx, dy| grid[(3 + dy) as usize][(3 + dx) as usize] = b'#');
+        assert_eq!(grid, [*b"..###..", *b".#...#.", *b"#.....#", *b"#.....#", *b"#.....#", *b".#...#.", *b"..###.."]);
+    }
+
+    #[test_case]
+    fn wrap_row_wraps_both_ways() {
+        assert_eq!(wrap_row(5, 2, 50), 3);
+        assert_eq!(wrap_row(1, 3, 50), 48);
+        assert_eq!(wrap_row(0, 0, 25), 0);
+        assert_eq!(wrap_row(49, -1, 50), 0);
+    }
+
+    #[test_case]
+    fn color_code_packs_foreground_and_background() {
+        assert_eq!(ColorCode::new(Color::White, Color::Black).0, 0x0f);
+        assert_eq!(ColorCode::new(Color::Yellow, Color::Blue).0, 0x1e);
+        assert_eq!(WHITE_ON_RED.blinking().0, 0xcf);
+    }
+
+    #[test_case]
+    fn rng_seeds_diverge() {
+        let (mut a, mut b) = (Rng::new(1), Rng::new(2));
+        assert!((0..4).any(|_| a.next_u32() != b.next_u32()));
+        let (mut a, mut b) = (Rng::new(42), Rng::new(42));
+        assert!((0..4).all(|_| a.next_u32() == b.next_u32()));
+    }
+
+    #[test_case]
+    fn border_cell_walks_clockwise() {
+        let (rows, cols) = (25, 80);
+        assert_eq!(border_cell(0, rows, cols), (0, 0));
+        assert_eq!(border_cell(79, rows, cols), (0, 79));
+        assert_eq!(border_cell(80, rows, cols), (1, 79));
+        assert_eq!(border_cell(103, rows, cols), (24, 79));
+        assert_eq!(border_cell(104, rows, cols), (24, 78));
+        assert_eq!(border_cell(border_len(rows, cols) - 1, rows, cols), (1, 0));
+        assert_eq!(border_cell(border_len(rows, cols), rows, cols), (0, 0));
+    }
+
+    // A 10x3 buffer: drawing clips at its own edges, not the screen's
+    #[test_case]
+    fn vga_draws_into_any_buffer() {
+        let mut cells = [0u16; 30];
+        let mut vga = unsafe { Vga::new(cells.as_mut_ptr() as *mut Volatile<u16>, 10, 3) };
+        vga.clear();
+        assert_eq!(vga.write_at_clipped(b"SWAG SWAG SWAG", 1, 4, YELLOW), 6);
+        vga.fill_region(2, 8, 5, 5, b'#', LIGHT_RED);
+        vga.write_char_at(b'X', 3, 0, WHITE); // below the last row
+        assert_eq!(vga.read_char_at(1, 4), (b'S', YELLOW));
+        assert_eq!(vga.read_char_at(2, 9), (b'#', LIGHT_RED));
+        assert_eq!(cells[19], vga_cell(b'S', YELLOW)); // end of the row...
+        assert_eq!(cells[20], BLANK_CELL); // ...and nothing spilled into the next
+        assert_eq!(cells[27..], [BLANK_CELL, vga_cell(b'#', LIGHT_RED), vga_cell(b'#', LIGHT_RED)]);
+    }
+
+    // Slots past the eighth get ready and stop bits of their own
+    #[test_case]
+    fn sixteen_slot_executor_runs_every_task() {
+        static FINISHED: AtomicUsize = AtomicUsize::new(0);
+        let mut executor: Executor<16, 64> = Executor::new();
+        let ids: [TaskId; 16] = core::array::from_fn(|_| {
+            executor
+                .spawn(async {
+                    yield_now().await;
+                    FINISHED.fetch_add(1, Ordering::Relaxed);
+                })
+                .unwrap()
+        });
+        assert_eq!(executor.spawn(async {}), Err(SpawnError::NoFreeSlot));
+        while !ids.iter().all(|&id| executor.is_finished(id)) {
+            executor.run_pass();
+        }
+        assert_eq!(FINISHED.load(Ordering::Relaxed), 16);
+    }
+
+    #[test_case]
+    fn request_stop_reaches_only_that_task() {
+        let mut executor = DefaultExecutor::new();
+        let [stopped, running] = [(); 2].map(|_| {
+            executor
+                .spawn(async {
+                    while !should_stop() {
+                        yield_now().await;
+                    }
+                })
+                .unwrap()
+        });
+        executor.run_pass();
+        executor.request_stop(stopped);
+        executor.run_pass();
+        assert!(executor.is_finished(stopped));
+        assert!(!executor.is_finished(running));
+        executor.cancel(running);
+    }
+
+    // A u128 held across an await lives in the task's storage, which has to be aligned for it
+    #[test_case]
+    fn task_storage_holds_aligned_futures() {
+        let mut executor = DefaultExecutor::new();
+        let id = executor
+            .spawn(async {
+                let wide: u128 = 1 << 100;
+                let narrow: u64 = 7;
+                yield_now().await;
+                assert_eq!(&wide as *const u128 as usize % core::mem::align_of::<u128>(), 0);
+                assert_eq!((wide >> 100, narrow), (1, 7));
+            })
+            .unwrap();
+        while !executor.is_finished(id) {
+            executor.run_pass();
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,123 @@
+// memcpy, memmove, memset and memcmp. The compiler emits calls to these for any big
+// enough copy, fill or comparison, and on a bare-metal target nothing provides them
+// unless core is rebuilt with the `compiler-builtins-mem` feature. With these the kernel
+// links without it.
+//
+// The copies and fills are `rep movsb`/`rep stosb`, which recent CPUs run about as fast
+// as an unrolled loop. A plain Rust loop could get turned back into a call to memcpy
+// by LLVM, which would then call itself forever.
+
+use core::arch::asm;
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn memcpy(dest: *mut u8, src: *const u8, n: usize) -> *mut u8 {
+    unsafe {
+        asm!(
+            "rep movsb",
+            inout("rcx") n => _,
+            inout("rdi") dest => _,
+            inout("rsi") src => _,
+            options(nostack, preserves_flags),
+        );
+    }
+    dest
+}
+
+// Overlap-safe: a forward copy is fine unless `dest` starts inside `src`, and then the
+// copy runs backwards (direction flag set) so no byte is overwritten before it's read.
+// Interrupt handlers clear the direction flag on entry, so one landing mid-copy is fine.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn memmove(dest: *mut u8, src: *const u8, n: usize) -> *mut u8 {
+    if n == 0 {
+        return dest;
+    }
+    let (to, from) = (dest as usize, src as usize);
+    if to <= from || to >= from + n {
+        return unsafe { memcpy(dest, src, n) };
+    }
+    unsafe {
+        asm!(
+            "std",
+            "rep movsb",
+            "cld",
+            inout("rcx") n => _,
+            inout("rdi") dest.add(n - 1) => _,
+            inout("rsi") src.add(n - 1) => _,
+            options(nostack),
+        );
+    }
+    dest
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn memset(dest: *mut u8, value: i32, n: usize) -> *mut u8 {
+    unsafe {
+        asm!(
+            "rep stosb",
+            inout("rcx") n => _,
+            inout("rdi") dest => _,
+            in("al") value as u8,
+            options(nostack, preserves_flags),
+        );
+    }
+    dest
+}
+
+// Compares as unsigned bytes, like C
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn memcmp(a: *const u8, b: *const u8, n: usize) -> i32 {
+    for i in 0..n {
+        let (x, y) = unsafe { (*a.add(i), *b.add(i)) };
+        if x != y {
+            return x as i32 - y as i32;
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counting() -> [u8; 16] {
+        core::array::from_fn(|i| i as u8)
+    }
+
+    // Overlapping both ways, against a copy through a separate buffer
+    #[test_case]
+    fn memmove_handles_overlap() {
+        for (dest, src) in [(0, 4), (4, 0), (2, 3), (3, 2), (5, 5)] {
+            let mut expected = counting();
+            let before = expected;
+            expected[dest..dest + 8].copy_from_slice(&before[src..src + 8]);
+            let mut actual = counting();
+            let base = actual.as_mut_ptr();
+            unsafe { memmove(base.add(dest), base.add(src), 8) };
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test_case]
+    fn memcpy_and_memset_match_loops() {
+        let source = counting();
+        let mut copied = [0u8; 16];
+        unsafe { memcpy(copied.as_mut_ptr(), source.as_ptr(), 16) };
+        assert_eq!(copied, source);
+
+        let mut filled = counting();
+        unsafe { memset(filled.as_mut_ptr().add(4), 0xab, 8) };
+        for (i, &byte) in filled.iter().enumerate() {
+            assert_eq!(byte, if (4..12).contains(&i) { 0xab } else { i as u8 });
+        }
+    }
+
+    #[test_case]
+    fn memcmp_orders_as_unsigned() {
+        let (a, b) = ([1u8, 2, 0x80], [1u8, 2, 0x01]);
+        unsafe {
+            assert_eq!(memcmp(a.as_ptr(), a.as_ptr(), 3), 0);
+            assert!(memcmp(a.as_ptr(), b.as_ptr(), 3) > 0);
+            assert!(memcmp(b.as_ptr(), a.as_ptr(), 3) < 0);
+        }
+    }
+}
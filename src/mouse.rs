@@ -0,0 +1,151 @@
+// PS/2 mouse on the 8042's auxiliary port, reported on IRQ12
+
+use core::sync::atomic::{AtomicI16, AtomicU8, AtomicUsize, Ordering};
+
+use crate::port::{inb, outb};
+
+const DATA_PORT: u16 = 0x60;
+const STATUS_PORT: u16 = 0x64; // reads status, writes controller commands
+
+const STATUS_OUTPUT_FULL: u8 = 0x01;
+const STATUS_INPUT_FULL: u8 = 0x02;
+
+const CMD_ENABLE_AUX: u8 = 0xa8;
+const CMD_READ_CONFIG: u8 = 0x20;
+const CMD_WRITE_CONFIG: u8 = 0x60;
+const CMD_WRITE_AUX: u8 = 0xd4; // next data byte goes to the mouse
+
+const CONFIG_AUX_IRQ: u8 = 0x02;
+const CONFIG_AUX_CLOCK_OFF: u8 = 0x20;
+
+const MOUSE_SET_DEFAULTS: u8 = 0xf6;
+const MOUSE_ENABLE_REPORTING: u8 = 0xf4;
+
+// First packet byte: buttons in bits 0-2, bit 3 always set, then sign and overflow bits
+const PACKET_ALWAYS_ONE: u8 = 0x08;
+const PACKET_X_SIGN: u8 = 0x10;
+const PACKET_Y_SIGN: u8 = 0x20;
+const PACKET_X_OVERFLOW: u8 = 0x40;
+const PACKET_Y_OVERFLOW: u8 = 0x80;
+const PACKET_BUTTONS: u8 = 0x07;
+
+// Mouse counts per text cell, roughly the size of a glyph in pixels
+const COUNTS_PER_COL: i16 = 8;
+const COUNTS_PER_ROW: i16 = 16;
+const MAX_X: i16 = 80 * COUNTS_PER_COL - 1;
+const MAX_Y: i16 = 25 * COUNTS_PER_ROW - 1;
+
+// Spins per wait before assuming there's no controller or no mouse
+const WAIT_SPINS: u32 = 100_000;
+
+static PACKET: [AtomicU8; 3] = [const { AtomicU8::new(0) }; 3];
+static PACKET_INDEX: AtomicUsize = AtomicUsize::new(0);
+
+// Position in mouse counts, starting in the middle of the screen
+static X: AtomicI16 = AtomicI16::new(MAX_X / 2);
+static Y: AtomicI16 = AtomicI16::new(MAX_Y / 2);
+static BUTTONS: AtomicU8 = AtomicU8::new(0);
+
+fn wait_input_empty() {
+    for _ in 0..WAIT_SPINS {
+        if unsafe { inb(STATUS_PORT) } & STATUS_INPUT_FULL == 0 {
+            return;
+        }
+        core::hint::spin_loop();
+    }
+}
+
+fn wait_output_full() -> bool {
+    for _ in 0..WAIT_SPINS {
+        if unsafe { inb(STATUS_PORT) } & STATUS_OUTPUT_FULL != 0 {
+            return true;
+        }
+        core::hint::spin_loop();
+    }
+    false
+}
+
+fn controller_command(command: u8) {
+    wait_input_empty();
+    unsafe { outb(STATUS_PORT, command) };
+}
+
+fn write_data(byte: u8) {
+    wait_input_empty();
+    unsafe { outb(DATA_PORT, byte) };
+}
+
+fn read_data() -> Option<u8> {
+    wait_output_full().then(|| unsafe { inb(DATA_PORT) })
+}
+
+// Send a command to the mouse itself and swallow its ACK (0xfa)
+fn mouse_command(command: u8) {
+    controller_command(CMD_WRITE_AUX);
+    write_data(command);
+    let _ = read_data();
+}
+
+// Enable the auxiliary device and its IRQ, then start packet reporting.
+// Run with interrupts disabled, before IRQ12 is unmasked.
+pub fn init() {
+    controller_command(CMD_ENABLE_AUX);
+
+    controller_command(CMD_READ_CONFIG);
+    let config = read_data().unwrap_or(0);
+    controller_command(CMD_WRITE_CONFIG);
+    write_data((config | CONFIG_AUX_IRQ) & !CONFIG_AUX_CLOCK_OFF);
+
+    mouse_command(MOUSE_SET_DEFAULTS);
+    mouse_command(MOUSE_ENABLE_REPORTING);
+}
+
+// Called from the IRQ12 handler with one byte of a 3-byte packet
+pub fn handle_interrupt() {
+    let byte = unsafe { inb(DATA_PORT) };
+    let index = PACKET_INDEX.load(Ordering::Relaxed);
+
+    // Bit 3 is always set in the first byte; if it isn't we're mid-packet, so wait for a real start
+    if index == 0 && byte & PACKET_ALWAYS_ONE == 0 {
+        return;
+    }
+
+    PACKET[index].store(byte, Ordering::Relaxed);
+    if index < 2 {
+        PACKET_INDEX.store(index + 1, Ordering::Relaxed);
+        return;
+    }
+    PACKET_INDEX.store(0, Ordering::Relaxed);
+
+    let flags = PACKET[0].load(Ordering::Relaxed);
+    BUTTONS.store(flags & PACKET_BUTTONS, Ordering::Relaxed);
+    if flags & (PACKET_X_OVERFLOW | PACKET_Y_OVERFLOW) != 0 {
+        return; // movement is garbage when either counter overflowed
+    }
+
+    // 9-bit two's complement deltas: the sign bit lives in the first byte
+    let mut dx = PACKET[1].load(Ordering::Relaxed) as i16;
+    let mut dy = PACKET[2].load(Ordering::Relaxed) as i16;
+    if flags & PACKET_X_SIGN != 0 {
+        dx -= 0x100;
+    }
+    if flags & PACKET_Y_SIGN != 0 {
+        dy -= 0x100;
+    }
+
+    // The mouse counts up as it moves away from you; screen rows count down
+    let x = (X.load(Ordering::Relaxed) + dx).clamp(0, MAX_X);
+    let y = (Y.load(Ordering::Relaxed) - dy).clamp(0, MAX_Y);
+    X.store(x, Ordering::Relaxed);
+    Y.store(y, Ordering::Relaxed);
+}
+
+// Cursor column and row on the text screen plus the buttons currently held: bit 0 left,
+// bit 1 right, bit 2 middle
+pub fn mouse_state() -> (i16, i16, u8) {
+    (
+        X.load(Ordering::Relaxed) / COUNTS_PER_COL,
+        Y.load(Ordering::Relaxed) / COUNTS_PER_ROW,
+        BUTTONS.load(Ordering::Relaxed),
+    )
+}
@@ -0,0 +1,71 @@
+// 8259 Programmable Interrupt Controllers (master + slave)
+
+use crate::port::{inb, io_wait, outb};
+
+const PIC1_COMMAND: u16 = 0x20;
+const PIC1_DATA: u16 = 0x21;
+const PIC2_COMMAND: u16 = 0xA0;
+const PIC2_DATA: u16 = 0xA1;
+
+const ICW1_INIT: u8 = 0x11; // Start init sequence, ICW4 follows
+const ICW4_8086: u8 = 0x01;
+const CMD_EOI: u8 = 0x20;
+
+// Hardware IRQs live right after the 32 CPU exception vectors
+pub const PIC1_OFFSET: u8 = 0x20;
+pub const PIC2_OFFSET: u8 = 0x28;
+
+pub const IRQ_TIMER: u8 = 0;
+pub const IRQ_KEYBOARD: u8 = 1;
+const IRQ_CASCADE: u8 = 2;
+pub const IRQ_MOUSE: u8 = 12;
+
+// Move IRQ 0-7 to `offset1..offset1+8` and IRQ 8-15 to `offset2..offset2+8`. Out of reset
+// the master uses vectors 0x08-0x0F, where the timer would look like a double fault, so
+// this has to happen before `sti`. Every IRQ is masked afterwards; unmask with `set_mask`.
+pub fn remap(offset1: u8, offset2: u8) {
+    unsafe {
+        outb(PIC1_COMMAND, ICW1_INIT);
+        io_wait();
+        outb(PIC2_COMMAND, ICW1_INIT);
+        io_wait();
+        outb(PIC1_DATA, offset1); // ICW2: vector offsets
+        io_wait();
+        outb(PIC2_DATA, offset2);
+        io_wait();
+        outb(PIC1_DATA, 1 << IRQ_CASCADE); // ICW3: slave sits on IRQ2
+        io_wait();
+        outb(PIC2_DATA, 2); // ICW3: slave cascade identity
+        io_wait();
+        outb(PIC1_DATA, ICW4_8086);
+        io_wait();
+        outb(PIC2_DATA, ICW4_8086);
+        io_wait();
+
+        outb(PIC1_DATA, 0xff);
+        outb(PIC2_DATA, 0xff);
+    }
+}
+
+// Acknowledge an IRQ so the PIC delivers the next one
+pub fn send_eoi(irq: u8) {
+    unsafe {
+        if irq >= 8 {
+            outb(PIC2_COMMAND, CMD_EOI);
+        }
+        outb(PIC1_COMMAND, CMD_EOI);
+    }
+}
+
+// Mask (disable) or unmask a single IRQ line. Unmasking a slave IRQ also opens the cascade.
+pub fn set_mask(irq: u8, masked: bool) {
+    let (port, bit) = if irq < 8 { (PIC1_DATA, irq) } else { (PIC2_DATA, irq - 8) };
+    unsafe {
+        let mask = inb(port);
+        outb(port, if masked { mask | (1 << bit) } else { mask & !(1 << bit) });
+        if irq >= 8 && !masked {
+            let master = inb(PIC1_DATA);
+            outb(PIC1_DATA, master & !(1 << IRQ_CASCADE));
+        }
+    }
+}
@@ -0,0 +1,22 @@
+// Raw x86 port I/O helpers
+
+use core::arch::asm;
+
+pub unsafe fn inb(port: u16) -> u8 {
+    let value: u8;
+    unsafe { asm!("in al, dx", out("al") value, in("dx") port, options(nomem, nostack)); }
+    value
+}
+
+pub unsafe fn outb(port: u16, value: u8) {
+    unsafe { asm!("out dx, al", in("dx") port, in("al") value, options(nomem, nostack)); }
+}
+
+pub unsafe fn outl(port: u16, value: u32) {
+    unsafe { asm!("out dx, eax", in("dx") port, in("eax") value, options(nomem, nostack)); }
+}
+
+// Writing to the unused POST port takes long enough for slow devices (PIC, CMOS) to settle
+pub unsafe fn io_wait() {
+    unsafe { outb(0x80, 0); }
+}
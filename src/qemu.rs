@@ -0,0 +1,19 @@
+// Ending a QEMU run from inside the kernel, for automated tests. QEMU has to be started
+// with `-device isa-debug-exit,iobase=0xf4,iosize=0x04`; anywhere else the write goes nowhere.
+
+use crate::port::outl;
+
+const ISA_DEBUG_EXIT_PORT: u16 = 0xf4;
+
+// QEMU exits with status (code << 1) | 1, so 33 for success and 35 for failure. Statuses
+// 0 and 1 are QEMU's own, which is why neither code is 0.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u32)]
+pub enum QemuExitCode {
+    Success = 0x10,
+    Failed = 0x11,
+}
+
+pub fn exit_qemu(code: QemuExitCode) {
+    unsafe { outl(ISA_DEBUG_EXIT_PORT, code as u32) };
+}
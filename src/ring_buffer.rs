@@ -0,0 +1,116 @@
+// Fixed-size single-producer/single-consumer queue that can live in a `static`
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+// `head` and `tail` count every pop and push ever made (wrapping), so the buffer holds
+// a full N items and `tail - head` is always the length. Only one context may push
+// and only one may pop; when one of them is an interrupt handler, the other side
+// should run inside `without_interrupts`.
+pub struct RingBuffer<T, const N: usize> {
+    slots: [UnsafeCell<MaybeUninit<T>>; N],
+    head: AtomicUsize, // total pops
+    tail: AtomicUsize, // total pushes
+}
+
+// Safety: a slot is only touched by the producer before `tail` publishes it and by the
+// consumer before `head` releases it, so values just move from one side to the other
+unsafe impl<T: Send, const N: usize> Sync for RingBuffer<T, N> {}
+
+impl<T, const N: usize> RingBuffer<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            slots: [const { UnsafeCell::new(MaybeUninit::uninit()) }; N],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    // Hands the value back if the buffer is full
+    pub fn push(&self, value: T) -> Result<(), T> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        if tail.wrapping_sub(self.head.load(Ordering::Acquire)) >= N {
+            return Err(value);
+        }
+        unsafe { (*self.slots[tail % N].get()).write(value) };
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        // The Acquire load of `tail` in here is what makes the pushed slot visible
+        if self.is_empty() {
+            return None;
+        }
+        let head = self.head.load(Ordering::Relaxed);
+        let value = unsafe { (*self.slots[head % N].get()).assume_init_read() };
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(value)
+    }
+
+    // Copy of the `index`th queued item (0 is the next one `pop` would return), left in place.
+    // Only the consumer side may peek.
+    pub fn peek(&self, index: usize) -> Option<T>
+    where
+        T: Copy,
+    {
+        let head = self.head.load(Ordering::Relaxed);
+        if index >= self.tail.load(Ordering::Acquire).wrapping_sub(head) {
+            return None;
+        }
+        Some(unsafe { (*self.slots[head.wrapping_add(index) % N].get()).assume_init_read() })
+    }
+
+    pub fn len(&self) -> usize {
+        self.tail.load(Ordering::Acquire).wrapping_sub(self.head.load(Ordering::Acquire))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() >= N
+    }
+}
+
+impl<T, const N: usize> Drop for RingBuffer<T, N> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn full_and_empty() {
+        let queue: RingBuffer<u8, 4> = RingBuffer::new();
+        assert!(queue.is_empty());
+        assert_eq!(queue.pop(), None);
+        for i in 0..4 {
+            assert_eq!(queue.push(i), Ok(()));
+        }
+        assert!(queue.is_full());
+        assert_eq!(queue.push(9), Err(9));
+        assert_eq!(queue.pop(), Some(0));
+        assert!(!queue.is_full());
+    }
+
+    #[test_case]
+    fn wraps_around() {
+        let queue: RingBuffer<u8, 4> = RingBuffer::new();
+        for round in 0..3u8 {
+            for i in 0..3 {
+                assert_eq!(queue.push(round * 10 + i), Ok(()));
+            }
+            assert_eq!(queue.peek(2), Some(round * 10 + 2));
+            for i in 0..3 {
+                assert_eq!(queue.pop(), Some(round * 10 + i));
+            }
+        }
+        assert!(queue.is_empty());
+    }
+}
@@ -0,0 +1,65 @@
+// 16550 UART on COM1, for debug output under QEMU (`-serial stdio`)
+
+use core::fmt;
+
+use crate::interrupts::without_interrupts;
+use crate::port::{inb, outb};
+
+pub const COM1: u16 = 0x3f8;
+
+const LINE_STATUS_TRANSMIT_EMPTY: u8 = 0x20;
+
+pub struct SerialPort(pub u16);
+
+impl SerialPort {
+    // 38400 baud, 8N1, FIFOs enabled, no UART interrupts
+    pub fn init(&mut self) {
+        let base = self.0;
+        unsafe {
+            outb(base + 1, 0x00); // disable interrupts
+            outb(base + 3, 0x80); // DLAB on to set the baud divisor
+            outb(base, 0x03); // divisor 3 = 38400 baud
+            outb(base + 1, 0x00);
+            outb(base + 3, 0x03); // DLAB off, 8 bits, no parity, one stop bit
+            outb(base + 2, 0xc7); // enable + clear FIFOs, 14-byte threshold
+            outb(base + 4, 0x0b); // DTR + RTS + OUT2
+        }
+    }
+
+    fn write_byte(&mut self, byte: u8) {
+        unsafe {
+            while inb(self.0 + 5) & LINE_STATUS_TRANSMIT_EMPTY == 0 {
+                core::hint::spin_loop();
+            }
+            outb(self.0, byte);
+        }
+    }
+}
+
+impl fmt::Write for SerialPort {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.write_byte(byte);
+        }
+        Ok(())
+    }
+}
+
+// Raw output for serial_print!, no timestamp or level. Interrupts are held off so a
+// handler that prints can't splice its bytes into ours.
+pub fn _print(args: fmt::Arguments) {
+    without_interrupts(|| {
+        let _ = fmt::Write::write_fmt(&mut SerialPort(COM1), args);
+    });
+}
+
+#[macro_export]
+macro_rules! serial_print {
+    ($($arg:tt)*) => { $crate::serial::_print(format_args!($($arg)*)) };
+}
+
+#[macro_export]
+macro_rules! serial_println {
+    () => { $crate::serial_print!("\n") };
+    ($($arg:tt)*) => { $crate::serial_print!("{}\n", format_args!($($arg)*)) };
+}
@@ -0,0 +1,62 @@
+// PC speaker, driven by PIT channel 2
+
+use crate::port::{inb, outb};
+use crate::timer::{self, PIT_BASE_FREQUENCY, PIT_COMMAND};
+
+const PIT_CHANNEL2: u16 = 0x42;
+const SPEAKER_PORT: u16 = 0x61;
+const SPEAKER_GATE: u8 = 0x01; // lets channel 2 count
+const SPEAKER_DATA: u8 = 0x02; // connects channel 2's output to the speaker
+
+// Start a square wave at `freq_hz`; it keeps sounding until `beep_off`
+pub fn beep(freq_hz: u32) {
+    let divisor = (PIT_BASE_FREQUENCY / freq_hz.max(1)).clamp(1, 0xffff) as u16;
+    unsafe {
+        outb(PIT_COMMAND, 0xb6); // channel 2, lobyte/hibyte, mode 3 (square wave)
+        outb(PIT_CHANNEL2, divisor as u8);
+        outb(PIT_CHANNEL2, (divisor >> 8) as u8);
+        outb(SPEAKER_PORT, inb(SPEAKER_PORT) | SPEAKER_GATE | SPEAKER_DATA);
+    }
+}
+
+pub fn beep_off() {
+    unsafe { outb(SPEAKER_PORT, inb(SPEAKER_PORT) & !(SPEAKER_GATE | SPEAKER_DATA)) };
+}
+
+// Silences the speaker when dropped, so a beep cut short by `timeout` or a finished
+// `join` doesn't go on forever
+struct BeepGuard;
+
+impl Drop for BeepGuard {
+    fn drop(&mut self) {
+        beep_off();
+    }
+}
+
+pub async fn beep_for_ms(freq_hz: u32, ms: u64) {
+    beep(freq_hz);
+    let _guard = BeepGuard;
+    timer::sleep_ms(ms).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DefaultExecutor;
+
+    fn speaker_on() -> bool {
+        unsafe { inb(SPEAKER_PORT) & (SPEAKER_GATE | SPEAKER_DATA) != 0 }
+    }
+
+    // Cancelling drops the task's future right where it's waiting, guard and all
+    #[test_case]
+    fn cancelled_beep_goes_quiet() {
+        let mut executor = DefaultExecutor::new();
+        let id = executor.spawn(beep_for_ms(440, 10_000)).unwrap();
+        executor.run_pass();
+        assert!(speaker_on());
+        executor.cancel(id);
+        assert!(executor.is_finished(id));
+        assert!(!speaker_on());
+    }
+}
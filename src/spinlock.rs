@@ -0,0 +1,84 @@
+// Spinlock for state shared between tasks and interrupt handlers
+
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::interrupts;
+
+pub struct SpinLock<T> {
+    locked: AtomicBool,
+    value: UnsafeCell<T>,
+}
+
+// Only one guard at a time ever hands out the value
+unsafe impl<T: Send> Sync for SpinLock<T> {}
+
+impl<T> SpinLock<T> {
+    pub const fn new(value: T) -> Self {
+        Self { locked: AtomicBool::new(false), value: UnsafeCell::new(value) }
+    }
+
+    // Interrupts stay off for as long as the guard lives. Otherwise a handler taking the
+    // lock could interrupt the code holding it and spin forever. So keep guards short,
+    // and never hold one across an `.await`.
+    pub fn lock(&self) -> SpinLockGuard<'_, T> {
+        let interrupts_were_enabled = interrupts::are_enabled();
+        interrupts::disable();
+        while self.locked.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            core::hint::spin_loop();
+        }
+        SpinLockGuard { lock: self, interrupts_were_enabled }
+    }
+}
+
+pub struct SpinLockGuard<'a, T> {
+    lock: &'a SpinLock<T>,
+    interrupts_were_enabled: bool,
+}
+
+impl<T> Deref for SpinLockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T> DerefMut for SpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<T> Drop for SpinLockGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.locked.store(false, Ordering::Release);
+        if self.interrupts_were_enabled {
+            interrupts::enable();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn lock_and_unlock() {
+        let lock = SpinLock::new(1);
+        *lock.lock() += 1;
+        assert_eq!(*lock.lock(), 2); // would spin forever if the first guard hadn't unlocked
+    }
+
+    #[test_case]
+    fn interrupts_off_while_held() {
+        let lock = SpinLock::new(());
+        let enabled = interrupts::are_enabled();
+        {
+            let _guard = lock.lock();
+            assert!(!interrupts::are_enabled());
+        }
+        assert_eq!(interrupts::are_enabled(), enabled);
+    }
+}
@@ -0,0 +1,323 @@
+// PIT-driven tick counter and tick-based futures
+
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use core::task::{Context, Poll, Waker};
+
+use crate::interrupts::without_interrupts;
+use crate::port::outb;
+
+pub const PIT_BASE_FREQUENCY: u32 = 1_193_182;
+const PIT_CHANNEL0: u16 = 0x40;
+pub const PIT_COMMAND: u16 = 0x43;
+
+pub const TICK_HZ: u32 = 100;
+
+static TICKS: AtomicU64 = AtomicU64::new(0);
+static HZ: AtomicU32 = AtomicU32::new(TICK_HZ);
+
+// Global animation speed: a Q8.8 multiplier on every `FrameLimiter`'s frame rate
+pub const SPEED_SCALE_ONE: u32 = 1 << 8;
+const MIN_SPEED_SCALE: u32 = SPEED_SCALE_ONE / 4;
+const MAX_SPEED_SCALE: u32 = SPEED_SCALE_ONE * 4;
+
+static SPEED_SCALE: AtomicU32 = AtomicU32::new(SPEED_SCALE_ONE);
+
+pub fn speed_scale() -> u32 {
+    SPEED_SCALE.load(Ordering::Relaxed)
+}
+
+// Clamped to 0.25x-4x, so no setting can freeze an app or have it flood the machine
+pub fn set_speed(scale: u32) {
+    SPEED_SCALE.store(scale.clamp(MIN_SPEED_SCALE, MAX_SPEED_SCALE), Ordering::Relaxed);
+}
+
+// Program PIT channel 0 to fire IRQ0 `hz` times per second
+pub fn init_pit(hz: u32) {
+    let divisor = (PIT_BASE_FREQUENCY / hz).clamp(1, 0xffff) as u16;
+    HZ.store(hz, Ordering::Relaxed);
+    unsafe {
+        outb(PIT_COMMAND, 0x36); // channel 0, lobyte/hibyte, mode 3 (square wave)
+        outb(PIT_CHANNEL0, divisor as u8);
+        outb(PIT_CHANNEL0, (divisor >> 8) as u8);
+    }
+}
+
+// Wakers waiting for a tick deadline. Each task waits on a deadline or two at a time
+// (a sleep inside a `timeout`), so this covers every executor slot.
+const MAX_TIMER_WAITERS: usize = 16;
+
+static mut TIMER_WAITERS: [Option<(u64, Waker)>; MAX_TIMER_WAITERS] = [const { None }; MAX_TIMER_WAITERS];
+
+// Called from the IRQ0 handler
+pub fn on_tick() {
+    let now = TICKS.fetch_add(1, Ordering::Relaxed) + 1;
+    let waiters = unsafe { &mut *core::ptr::addr_of_mut!(TIMER_WAITERS) };
+    for waiter in waiters.iter_mut().filter(|waiter| matches!(waiter, Some((deadline, _)) if *deadline <= now)) {
+        if let Some((_, waker)) = waiter.take() {
+            waker.wake();
+        }
+    }
+}
+
+// Have the timer interrupt wake `waker` once the tick counter reaches `deadline`
+pub fn wake_at(deadline: u64, waker: &Waker) {
+    without_interrupts(|| {
+        if ticks() >= deadline {
+            waker.wake_by_ref(); // already due, maybe passed while we were getting here
+            return;
+        }
+        let waiters = unsafe { &mut *core::ptr::addr_of_mut!(TIMER_WAITERS) };
+        // The same task waiting again keeps one entry, for whichever deadline comes first
+        if let Some((at, _)) = waiters.iter_mut().flatten().find(|(_, w)| w.will_wake(waker)) {
+            *at = (*at).min(deadline);
+            return;
+        }
+        match waiters.iter_mut().find(|waiter| waiter.is_none()) {
+            Some(free) => *free = Some((deadline, waker.clone())),
+            None => waker.wake_by_ref(), // table full: get polled again next pass instead
+        }
+    });
+}
+
+// Ticks since the PIT was started
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+// Milliseconds since the PIT was started
+pub fn uptime_ms() -> u64 {
+    ticks() * 1000 / HZ.load(Ordering::Relaxed) as u64
+}
+
+// CPU timestamp counter, for timing things far shorter than a tick
+pub fn rdtsc() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+// Counts TSC cycles from `start`. Cycles aren't a fixed unit of time, but they're
+// fine for comparing one version of a loop against another on the same machine.
+#[derive(Clone, Copy)]
+pub struct Stopwatch {
+    start: u64,
+}
+
+impl Stopwatch {
+    pub fn start() -> Self {
+        Self { start: rdtsc() }
+    }
+
+    pub fn elapsed_cycles(&self) -> u64 {
+        rdtsc().wrapping_sub(self.start)
+    }
+}
+
+// Rounds up so a non-zero duration never becomes zero ticks
+pub fn ms_to_ticks(ms: u64) -> u64 {
+    (ms * HZ.load(Ordering::Relaxed) as u64).div_ceil(1000)
+}
+
+// Fires every `period` ticks on a fixed schedule. Deadlines are absolute, so time
+// spent between ticks doesn't push the following ticks back.
+pub struct Interval {
+    period: u64,
+    deadline: u64,
+}
+
+impl Interval {
+    pub fn new(period_ticks: u64) -> Self {
+        let period = period_ticks.max(1);
+        Self { period, deadline: ticks() + period }
+    }
+
+    pub fn new_ms(ms: u64) -> Self {
+        Self::new(ms_to_ticks(ms))
+    }
+
+    pub fn tick(&mut self) -> Tick<'_> {
+        Tick { interval: self }
+    }
+}
+
+pub struct Tick<'a> {
+    interval: &'a mut Interval,
+}
+
+impl Future for Tick<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let now = ticks();
+        let interval = &mut *self.interval;
+        if now < interval.deadline {
+            wake_at(interval.deadline, cx.waker());
+            return Poll::Pending;
+        }
+
+        interval.deadline += interval.period;
+        if interval.deadline <= now {
+            // We fell more than a whole period behind - skip the missed ticks instead of firing a burst
+            let missed = (now - interval.deadline) / interval.period + 1;
+            interval.deadline += missed * interval.period;
+        }
+        Poll::Ready(())
+    }
+}
+
+// Completes once the tick counter reaches `deadline`
+pub struct SleepUntil {
+    deadline: u64,
+}
+
+impl Future for SleepUntil {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if ticks() >= self.deadline {
+            Poll::Ready(())
+        } else {
+            wake_at(self.deadline, cx.waker());
+            Poll::Pending
+        }
+    }
+}
+
+pub fn sleep_until(deadline: u64) -> SleepUntil {
+    SleepUntil { deadline }
+}
+
+// Sleep for `n` ticks. The timer interrupt wakes the task at the deadline; until then
+// the executor skips it, and halts the CPU if every task is asleep.
+pub fn sleep_ticks(n: u64) -> SleepUntil {
+    sleep_until(ticks() + n)
+}
+
+// Sleep for at least `ms` milliseconds of PIT time, the same on any machine
+pub async fn sleep_ms(ms: u64) {
+    sleep_ticks(ms_to_ticks(ms)).await;
+}
+
+// Paces a render loop to a frame rate using the tick counter. Frame deadlines are
+// computed from the start time (frame * hz / fps), so fractional tick budgets like
+// 100 Hz / 30 fps average out exactly instead of rounding every frame.
+// The global speed scale multiplies the frame rate.
+pub struct FrameLimiter {
+    frames: u32, // the rate is `frames` frames every `per_ms` milliseconds
+    per_ms: u32,
+    scale: u64, // speed scale the current schedule was started with
+    start: u64,
+    frame: u64,
+    window_start: u64,
+    window_frames: u64,
+    fps: u32,
+}
+
+impl FrameLimiter {
+    pub fn new(target_fps: u32) -> Self {
+        Self::with_rate(target_fps.max(1), 1000)
+    }
+
+    // One frame every `ms` milliseconds, for rates that aren't a whole number per second
+    pub fn every_ms(ms: u64) -> Self {
+        Self::with_rate(1, ms.clamp(1, u32::MAX as u64) as u32)
+    }
+
+    fn with_rate(frames: u32, per_ms: u32) -> Self {
+        let now = ticks();
+        Self {
+            frames,
+            per_ms,
+            scale: speed_scale() as u64,
+            start: now,
+            frame: 0,
+            window_start: now,
+            window_frames: 0,
+            fps: 0,
+        }
+    }
+
+    // Sleep for whatever is left of this frame's budget after the frame's work
+    pub async fn wait_for_next_frame(&mut self) {
+        let hz = HZ.load(Ordering::Relaxed) as u64;
+
+        // A new speed starts a new schedule; keeping the old start would jump or stall
+        let scale = speed_scale() as u64;
+        if scale != self.scale {
+            self.scale = scale;
+            self.start = ticks();
+            self.frame = 0;
+        }
+
+        // Scaled frame budget in ticks is hz / (fps * scale / ONE), fps being frames * 1000 / per_ms
+        let ticks_per_frame = |frames: u64| frames * hz * SPEED_SCALE_ONE as u64 * self.per_ms as u64 / (self.frames as u64 * 1000 * scale);
+        self.frame += 1;
+        let mut deadline = self.start + ticks_per_frame(self.frame);
+
+        // More than a frame behind: restart the schedule rather than rushing to catch up
+        let now = ticks();
+        if now > deadline + ticks_per_frame(1) {
+            self.start = now;
+            self.frame = 0;
+            deadline = now;
+        }
+
+        sleep_until(deadline).await;
+        self.count_frame(hz);
+    }
+
+    // Frames per second actually achieved, measured over the last second
+    pub fn fps(&self) -> u32 {
+        self.fps
+    }
+
+    fn count_frame(&mut self, hz: u64) {
+        self.window_frames += 1;
+        let elapsed = ticks() - self.window_start;
+        if elapsed >= hz {
+            self.fps = (self.window_frames * hz / elapsed) as u32;
+            self.window_start += elapsed;
+            self.window_frames = 0;
+        }
+    }
+}
+
+// Returned by `timeout` when the deadline passes before the future completes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeout;
+
+pub struct WithTimeout<F> {
+    future: Option<F>,
+    deadline: u64,
+}
+
+impl<F: Future> Future for WithTimeout<F> {
+    type Output = Result<F::Output, Timeout>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: `future` is never moved out; it is only polled in place or dropped in place
+        let this = unsafe { self.get_unchecked_mut() };
+        let Some(future) = this.future.as_mut() else {
+            return Poll::Ready(Err(Timeout));
+        };
+
+        // The inner future gets one last chance before the deadline is checked
+        if let Poll::Ready(output) = unsafe { Pin::new_unchecked(future) }.poll(cx) {
+            this.future = None;
+            return Poll::Ready(Ok(output));
+        }
+
+        if ticks() >= this.deadline {
+            this.future = None; // runs the inner future's Drop
+            Poll::Ready(Err(Timeout))
+        } else {
+            wake_at(this.deadline, cx.waker());
+            Poll::Pending
+        }
+    }
+}
+
+// Run `future` for at most `ticks` timer ticks
+pub fn timeout<F: Future>(ticks: u64, future: F) -> WithTimeout<F> {
+    WithTimeout { future: Some(future), deadline: self::ticks() + ticks }
+}
@@ -0,0 +1,46 @@
+// Dissolve transitions between screens
+
+use crate::{BLANK_CELL, MAX_SCREEN_CELLS, Vga, random, timer};
+
+// A third of a second at the default tick rate
+pub const DEFAULT_TICKS: u64 = 30;
+
+// What `fade_in` is revealing
+static mut TARGET: [u16; MAX_SCREEN_CELLS] = [BLANK_CELL; MAX_SCREEN_CELLS];
+
+// Every tick, each cell flips to `target` with a chance that grows from 0 to 1 over
+// `duration` ticks. The last step sets all of them, so nothing is left behind.
+async fn dissolve(duration: u64, target: impl Fn(usize) -> u16) {
+    let duration = duration.max(1);
+    let start = timer::ticks();
+    loop {
+        let elapsed = timer::ticks() - start;
+        let done = elapsed >= duration;
+        for (i, cell) in Vga::screen().cells().iter_mut().enumerate() {
+            if done || (random() as u64 % duration) < elapsed {
+                cell.write(target(i));
+            }
+        }
+        if done {
+            return;
+        }
+        timer::sleep_ticks(1).await;
+    }
+}
+
+// Blank the screen a few random cells at a time
+pub async fn fade_out(ticks: u64) {
+    dissolve(ticks, |_| BLANK_CELL).await;
+}
+
+// Reveal whatever `draw` puts on the screen, starting from black. `draw` writes
+// straight to the screen, which is captured and blanked again before the next frame.
+pub async fn fade_in(ticks: u64, draw: impl FnOnce()) {
+    let target = unsafe { &mut *core::ptr::addr_of_mut!(TARGET) };
+    draw();
+    for (saved, cell) in target.iter_mut().zip(Vga::screen().cells().iter_mut()) {
+        *saved = cell.read();
+        cell.write(BLANK_CELL);
+    }
+    dissolve(ticks, |i| target[i]).await;
+}
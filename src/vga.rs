@@ -0,0 +1,386 @@
+// VGA text mode geometry: 80x25 with the BIOS 8x16 font, or 80x50 with the BIOS 8x8
+// font. Both keep the 400-scanline timing, only the font height changes.
+// Also mode 13h (320x200, 256 colors), set up by programming the registers directly.
+
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::interrupts::without_interrupts;
+use crate::port::{inb, outb};
+
+const SEQ_INDEX: u16 = 0x3c4;
+const GC_INDEX: u16 = 0x3ce;
+const CRTC_INDEX: u16 = 0x3d4; // each data port sits right after its index port
+const ATTR_INDEX: u16 = 0x3c0; // index and data share this port, toggled by a flip-flop
+const MISC_OUTPUT_WRITE: u16 = 0x3c2;
+const DAC_READ_INDEX: u16 = 0x3c7;
+const DAC_WRITE_INDEX: u16 = 0x3c8;
+const DAC_DATA: u16 = 0x3c9;
+const ATTR_DATA_READ: u16 = 0x3c1;
+const INPUT_STATUS_1: u16 = 0x3da; // reading it resets the attribute flip-flop to "index"
+
+const SEQ_MAP_MASK: u8 = 0x02;
+const SEQ_CHAR_MAP_SELECT: u8 = 0x03;
+const SEQ_MEMORY_MODE: u8 = 0x04;
+const GC_READ_MAP: u8 = 0x04;
+const GC_MODE: u8 = 0x05;
+const GC_MISC: u8 = 0x06;
+const CRTC_MAX_SCAN_LINE: u8 = 0x09;
+const CRTC_CURSOR_START: u8 = 0x0a;
+const CRTC_CURSOR_END: u8 = 0x0b;
+const CRTC_CURSOR_LOCATION_HIGH: u8 = 0x0e;
+const CRTC_CURSOR_LOCATION_LOW: u8 = 0x0f;
+const ATTR_MODE_CONTROL: u8 = 0x10;
+
+const ATTR_PALETTE_SOURCE: u8 = 0x20; // leave set when picking an index, or the screen blanks
+const MODE_CONTROL_BLINK: u8 = 0x08;
+const CRTC_PROTECT: u8 = 0x80; // in register 0x11: write-protects CRTC registers 0-7
+const CURSOR_DISABLE: u8 = 0x20; // in the cursor start register
+const CURSOR_SCANLINE_MASK: u8 = 0x1f;
+
+// While plane 2 is mapped for font access, glyph `n` of block 0 starts at
+// FONT_MEMORY + n * GLYPH_STRIDE, whatever the font height
+const FONT_MEMORY: usize = 0xa0000;
+const GLYPH_STRIDE: usize = 32;
+const FONT_BLOCK_1: usize = 0x4000;
+
+// The VGA BIOS keeps an 8x8 font in its ROM, 256 glyphs of 8 bytes each, for graphics
+// modes. It points interrupt vector 0x1f at the second half (characters 128-255), which
+// is how we find the font without calling int 10h.
+const IVT_FONT_8X8_HIGH: usize = 0x1f * 4;
+const FONT_8X8_BYTES: usize = 256 * 8;
+const ROM_AREA: core::ops::Range<usize> = 0xc0000..0x100000;
+
+// Character map select values: maps A and B both on block 0 (the BIOS font) or block 1
+const CHAR_MAP_BLOCK_0: u8 = 0x00;
+const CHAR_MAP_BLOCK_1: u8 = 0x05;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    T80x25,
+    T80x50,
+}
+
+static SCREEN_ROWS: AtomicUsize = AtomicUsize::new(25);
+static FONT_8X8_READY: AtomicBool = AtomicBool::new(false);
+
+pub fn screen_rows() -> usize {
+    SCREEN_ROWS.load(Ordering::Relaxed)
+}
+
+pub fn screen_cols() -> usize {
+    80
+}
+
+// Most rows any mode can have, for sizing buffers
+pub const MAX_ROWS: usize = 50;
+
+fn write_register(index_port: u16, index: u8, value: u8) {
+    unsafe {
+        outb(index_port, index);
+        outb(index_port + 1, value);
+    }
+}
+
+fn read_register(index_port: u16, index: u8) -> u8 {
+    unsafe {
+        outb(index_port, index);
+        inb(index_port + 1)
+    }
+}
+
+// Map font plane 2 at 0xa0000 for `f`, then put text mode addressing back.
+// Plane 2 is only reachable there while odd/even addressing is switched off.
+fn with_font_plane(f: impl FnOnce(*mut u8)) {
+    write_register(SEQ_INDEX, SEQ_MAP_MASK, 0x04); // write plane 2 only
+    write_register(SEQ_INDEX, SEQ_MEMORY_MODE, 0x07); // sequential addressing
+    write_register(GC_INDEX, GC_READ_MAP, 0x02); // read plane 2
+    write_register(GC_INDEX, GC_MODE, 0x00); // no odd/even
+    write_register(GC_INDEX, GC_MISC, 0x04); // 64K window at 0xa0000
+
+    f(FONT_MEMORY as *mut u8);
+
+    // Back to normal text mode addressing at 0xb8000
+    write_register(SEQ_INDEX, SEQ_MAP_MASK, 0x03);
+    write_register(SEQ_INDEX, SEQ_MEMORY_MODE, 0x03);
+    write_register(GC_INDEX, GC_READ_MAP, 0x00);
+    write_register(GC_INDEX, GC_MODE, 0x10);
+    write_register(GC_INDEX, GC_MISC, 0x0e);
+}
+
+// Where the BIOS's 8x8 font is, if the vector leads into ROM the way it should
+fn rom_8x8_font() -> Option<*const u8> {
+    // Real mode far pointer: offset in the low half, segment in the high half
+    let vector = unsafe { (IVT_FONT_8X8_HIGH as *const u32).read_volatile() } as usize;
+    let high_half = ((vector >> 16) << 4) + (vector & 0xffff);
+    let start = high_half.checked_sub(FONT_8X8_BYTES / 2)?;
+    let in_rom = ROM_AREA.contains(&start) && ROM_AREA.contains(&(start + FONT_8X8_BYTES - 1));
+    in_rom.then_some(start as *const u8)
+}
+
+// Copy the BIOS's 8x8 font into block 1
+fn load_8x8_font() {
+    let Some(rom) = rom_8x8_font() else {
+        log_warn!("no 8x8 font in the VGA BIOS, halving the 8x16 one");
+        halve_8x16_font();
+        return;
+    };
+    with_font_plane(|font| {
+        for glyph in 0..256 {
+            for line in 0..8 {
+                unsafe {
+                    let byte = rom.add(glyph * 8 + line).read_volatile();
+                    font.add(FONT_BLOCK_1 + glyph * GLYPH_STRIDE + line).write_volatile(byte);
+                }
+            }
+        }
+    });
+}
+
+// Fallback for a BIOS without the vector: halve the 8x16 font into block 1, OR-ing each
+// pair of scanlines so thin strokes survive
+fn halve_8x16_font() {
+    with_font_plane(|font| {
+        for glyph in 0..256 {
+            for line in 0..8 {
+                unsafe {
+                    let source = font.add(glyph * GLYPH_STRIDE + line * 2);
+                    let merged = source.read_volatile() | source.add(1).read_volatile();
+                    font.add(FONT_BLOCK_1 + glyph * GLYPH_STRIDE + line).write_volatile(merged);
+                }
+            }
+        }
+    });
+}
+
+// Switch the number of text rows, or back to text from mode 13h. Text already on
+// screen is garbage afterwards, so clear it before drawing.
+pub fn set_text_mode(mode: Mode) {
+    let (font_height, char_map, rows) = match mode {
+        Mode::T80x25 => (16, CHAR_MAP_BLOCK_0, 25),
+        Mode::T80x50 => (8, CHAR_MAP_BLOCK_1, 50),
+    };
+
+    without_interrupts(|| {
+        if IN_MODE13.swap(false, Ordering::Relaxed) {
+            leave_mode13();
+        }
+        if mode == Mode::T80x50 && !FONT_8X8_READY.swap(true, Ordering::Relaxed) {
+            load_8x8_font();
+        }
+        write_register(SEQ_INDEX, SEQ_CHAR_MAP_SELECT, char_map);
+
+        // Only the low five bits of these hold the scanline; the rest are flags to keep
+        let max_scan_line = read_register(CRTC_INDEX, CRTC_MAX_SCAN_LINE);
+        write_register(CRTC_INDEX, CRTC_MAX_SCAN_LINE, (max_scan_line & 0xe0) | (font_height - 1));
+        let cursor_start = read_register(CRTC_INDEX, CRTC_CURSOR_START);
+        write_register(CRTC_INDEX, CRTC_CURSOR_START, (cursor_start & 0xe0) | (font_height - 2));
+        let cursor_end = read_register(CRTC_INDEX, CRTC_CURSOR_END);
+        write_register(CRTC_INDEX, CRTC_CURSOR_END, (cursor_end & 0xe0) | (font_height - 1));
+    });
+
+    SCREEN_ROWS.store(rows, Ordering::Relaxed);
+}
+
+// Move the blinking hardware cursor. Positions off the screen are clamped to its edge.
+pub fn set_cursor(row: usize, col: usize) {
+    let position = row.min(screen_rows() - 1) * 80 + col.min(screen_cols() - 1);
+    without_interrupts(|| {
+        write_register(CRTC_INDEX, CRTC_CURSOR_LOCATION_HIGH, (position >> 8) as u8);
+        write_register(CRTC_INDEX, CRTC_CURSOR_LOCATION_LOW, position as u8);
+    });
+}
+
+// Show the hardware cursor over scanlines `start..=end` of the character cell
+pub fn enable_cursor(start: u8, end: u8) {
+    without_interrupts(|| {
+        let cursor_start = read_register(CRTC_INDEX, CRTC_CURSOR_START);
+        let flags = cursor_start & !(CURSOR_SCANLINE_MASK | CURSOR_DISABLE);
+        write_register(CRTC_INDEX, CRTC_CURSOR_START, flags | (start & CURSOR_SCANLINE_MASK));
+        let cursor_end = read_register(CRTC_INDEX, CRTC_CURSOR_END);
+        write_register(CRTC_INDEX, CRTC_CURSOR_END, (cursor_end & !CURSOR_SCANLINE_MASK) | (end & CURSOR_SCANLINE_MASK));
+    });
+}
+
+// An underline in the bottom two scanlines; text modes all have 400 of them
+pub fn enable_underline_cursor() {
+    let font_height = (400 / screen_rows()) as u8;
+    enable_cursor(font_height - 2, font_height - 1);
+}
+
+pub fn disable_cursor() {
+    without_interrupts(|| {
+        let cursor_start = read_register(CRTC_INDEX, CRTC_CURSOR_START);
+        write_register(CRTC_INDEX, CRTC_CURSOR_START, cursor_start | CURSOR_DISABLE);
+    });
+}
+
+// Choose what bit 7 of a text attribute means. With blink on (the BIOS default) it makes
+// the character blink and backgrounds only have the eight dark colors; with blink off
+// it's the background's intensity bit, giving all sixteen background colors.
+pub fn enable_blink(on: bool) {
+    without_interrupts(|| unsafe {
+        inb(INPUT_STATUS_1);
+        outb(ATTR_INDEX, ATTR_PALETTE_SOURCE | ATTR_MODE_CONTROL);
+        let mode = inb(ATTR_DATA_READ);
+        let mode = if on { mode | MODE_CONTROL_BLINK } else { mode & !MODE_CONTROL_BLINK };
+        outb(ATTR_INDEX, mode); // the flip-flop is on "data" now
+    });
+}
+
+// Mode 13h: 320x200 at one byte per pixel, chained across all four planes
+
+pub const MODE13_WIDTH: usize = 320;
+pub const MODE13_HEIGHT: usize = 200;
+const MODE13_MEMORY: usize = 0xa0000;
+
+const FONT_GLYPH_HEIGHT: usize = 16;
+
+// Register values for each mode, in order: miscellaneous output, 5 sequencer,
+// 25 CRT controller, 9 graphics controller and 21 attribute controller registers
+struct ModeRegisters {
+    misc: u8,
+    sequencer: [u8; 5],
+    crtc: [u8; 25],
+    graphics: [u8; 9],
+    attribute: [u8; 21],
+}
+
+const MODE13_REGISTERS: ModeRegisters = ModeRegisters {
+    misc: 0x63,
+    sequencer: [0x03, 0x01, 0x0f, 0x00, 0x0e],
+    crtc: [
+        0x5f, 0x4f, 0x50, 0x82, 0x54, 0x80, 0xbf, 0x1f, 0x00, 0x41, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x9c, 0x0e, 0x8f, 0x28, 0x40, 0x96, 0xb9, 0xa3, 0xff,
+    ],
+    graphics: [0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x05, 0x0f, 0xff],
+    attribute: [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c,
+        0x0d, 0x0e, 0x0f, 0x41, 0x00, 0x0f, 0x00, 0x00,
+    ],
+};
+
+const TEXT_80X25_REGISTERS: ModeRegisters = ModeRegisters {
+    misc: 0x67,
+    sequencer: [0x03, 0x00, 0x03, 0x00, 0x02],
+    crtc: [
+        0x5f, 0x4f, 0x50, 0x82, 0x55, 0x81, 0xbf, 0x1f, 0x00, 0x4f, 0x0d, 0x0e, 0x00,
+        0x00, 0x00, 0x50, 0x9c, 0x0e, 0x8f, 0x28, 0x1f, 0x96, 0xb9, 0xa3, 0xff,
+    ],
+    graphics: [0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x0e, 0x00, 0xff],
+    attribute: [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x14, 0x07, 0x38, 0x39, 0x3a, 0x3b, 0x3c,
+        0x3d, 0x3e, 0x3f, 0x0c, 0x00, 0x0f, 0x08, 0x00,
+    ],
+};
+
+static IN_MODE13: AtomicBool = AtomicBool::new(false);
+
+// Mode 13h pixels land in every plane, wiping the BIOS font, and the plasma repaints
+// the DAC, so both are saved on the way in and put back on the way out
+static mut SAVED_FONT: [u8; 256 * FONT_GLYPH_HEIGHT] = [0; 256 * FONT_GLYPH_HEIGHT];
+static mut SAVED_DAC: [u8; 256 * 3] = [0; 256 * 3];
+
+fn write_mode_registers(registers: &ModeRegisters) {
+    unsafe {
+        outb(MISC_OUTPUT_WRITE, registers.misc);
+        for (index, &value) in registers.sequencer.iter().enumerate() {
+            write_register(SEQ_INDEX, index as u8, value);
+        }
+
+        // CRTC registers 0-7 are write-protected until bit 7 of register 0x11 is cleared
+        let protect = read_register(CRTC_INDEX, 0x11);
+        write_register(CRTC_INDEX, 0x11, protect & !CRTC_PROTECT);
+        for (index, &value) in registers.crtc.iter().enumerate() {
+            let value = if index == 0x11 { value & !CRTC_PROTECT } else { value };
+            write_register(CRTC_INDEX, index as u8, value);
+        }
+
+        for (index, &value) in registers.graphics.iter().enumerate() {
+            write_register(GC_INDEX, index as u8, value);
+        }
+        for (index, &value) in registers.attribute.iter().enumerate() {
+            inb(INPUT_STATUS_1);
+            outb(ATTR_INDEX, index as u8);
+            outb(ATTR_INDEX, value);
+        }
+        inb(INPUT_STATUS_1);
+        outb(ATTR_INDEX, ATTR_PALETTE_SOURCE); // palette done, turn the display back on
+    }
+}
+
+fn save_dac(dac: &mut [u8; 256 * 3]) {
+    unsafe {
+        outb(DAC_READ_INDEX, 0);
+        for component in dac.iter_mut() {
+            *component = inb(DAC_DATA);
+        }
+    }
+}
+
+fn restore_dac(dac: &[u8; 256 * 3]) {
+    unsafe {
+        outb(DAC_WRITE_INDEX, 0);
+        for &component in dac.iter() {
+            outb(DAC_DATA, component);
+        }
+    }
+}
+
+// Goes back to 80x25 when dropped, so an app that switched modes leaves the menu in
+// text mode even when it's cancelled mid-frame instead of returning
+pub struct RestoreTextMode;
+
+impl Drop for RestoreTextMode {
+    fn drop(&mut self) {
+        set_text_mode(Mode::T80x25);
+    }
+}
+
+// Switch to 320x200 with one byte per pixel at 0xa0000. `set_text_mode` switches back.
+pub fn set_mode13() {
+    without_interrupts(|| {
+        if IN_MODE13.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        let font = unsafe { &mut *core::ptr::addr_of_mut!(SAVED_FONT) };
+        with_font_plane(|plane| {
+            for (glyph, lines) in font.chunks_exact_mut(FONT_GLYPH_HEIGHT).enumerate() {
+                for (line, byte) in lines.iter_mut().enumerate() {
+                    *byte = unsafe { plane.add(glyph * GLYPH_STRIDE + line).read_volatile() };
+                }
+            }
+        });
+        save_dac(unsafe { &mut *core::ptr::addr_of_mut!(SAVED_DAC) });
+        write_mode_registers(&MODE13_REGISTERS);
+    });
+}
+
+fn leave_mode13() {
+    write_mode_registers(&TEXT_80X25_REGISTERS);
+    restore_dac(unsafe { &*core::ptr::addr_of!(SAVED_DAC) });
+    let font = unsafe { &*core::ptr::addr_of!(SAVED_FONT) };
+    with_font_plane(|plane| {
+        for (glyph, lines) in font.chunks_exact(FONT_GLYPH_HEIGHT).enumerate() {
+            for (line, &byte) in lines.iter().enumerate() {
+                unsafe { plane.add(glyph * GLYPH_STRIDE + line).write_volatile(byte) };
+            }
+        }
+    });
+}
+
+// Set one pixel in mode 13h; off-screen coordinates are ignored
+pub fn put_pixel13(x: usize, y: usize, color: u8) {
+    if x < MODE13_WIDTH && y < MODE13_HEIGHT {
+        unsafe { ((MODE13_MEMORY + y * MODE13_WIDTH + x) as *mut u8).write_volatile(color) };
+    }
+}
+
+// Load DAC entry `index` with a color; each component is 6 bits (0-63)
+pub fn set_palette_color(index: u8, r: u8, g: u8, b: u8) {
+    unsafe {
+        outb(DAC_WRITE_INDEX, index);
+        outb(DAC_DATA, r & 0x3f);
+        outb(DAC_DATA, g & 0x3f);
+        outb(DAC_DATA, b & 0x3f);
+    }
+}
@@ -0,0 +1,16 @@
+// Wrapper whose reads and writes always reach memory, in program order. For memory-mapped
+// hardware such as the VGA text buffer, where the compiler can't see anyone reading.
+
+#[derive(Clone, Copy)]
+#[repr(transparent)]
+pub struct Volatile<T: Copy>(T);
+
+impl<T: Copy> Volatile<T> {
+    pub fn read(&self) -> T {
+        unsafe { core::ptr::read_volatile(&self.0) }
+    }
+
+    pub fn write(&mut self, value: T) {
+        unsafe { core::ptr::write_volatile(&mut self.0, value) }
+    }
+}
@@ -0,0 +1,35 @@
+// Turns a hung main loop into a panic. The executor pets the watchdog on every pass and
+// the timer interrupt checks how long ago that was, so a task that never returns from
+// `poll` can't freeze the machine silently.
+
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::timer;
+
+pub const DEFAULT_TIMEOUT_MS: u64 = 5_000;
+
+static ARMED: AtomicBool = AtomicBool::new(false);
+static TIMEOUT_TICKS: AtomicU64 = AtomicU64::new(0);
+static LAST_HEARTBEAT: AtomicU64 = AtomicU64::new(0);
+
+// Start watching; from now on the loop has `timeout_ms` between pets
+pub fn start(timeout_ms: u64) {
+    TIMEOUT_TICKS.store(timer::ms_to_ticks(timeout_ms), Ordering::Relaxed);
+    watchdog_pet();
+    ARMED.store(true, Ordering::Relaxed);
+}
+
+// "Still alive". Code that does long stretches of work without yielding should call
+// this now and then.
+pub fn watchdog_pet() {
+    LAST_HEARTBEAT.store(timer::ticks(), Ordering::Relaxed);
+}
+
+// Called from the timer interrupt. Fires once; the panic screen keeps running with
+// interrupts on, so later ticks must not panic again.
+pub fn check() {
+    let stalled = timer::ticks().saturating_sub(LAST_HEARTBEAT.load(Ordering::Relaxed));
+    if stalled > TIMEOUT_TICKS.load(Ordering::Relaxed) && ARMED.swap(false, Ordering::Relaxed) {
+        panic!("WATCHDOG - main loop stalled for {} ticks", stalled);
+    }
+}